@@ -0,0 +1,18 @@
+-- Postgres schema for storage_backend = "postgres"
+CREATE TABLE IF NOT EXISTS logs (
+    id BIGSERIAL PRIMARY KEY,
+    -- NULL when the entry's timestamp was not valid RFC 3339
+    timestamp TIMESTAMPTZ,
+    service TEXT NOT NULL,
+    level TEXT NOT NULL,
+    message TEXT NOT NULL,
+    metadata JSONB,
+    trace_id TEXT,
+    span_id TEXT,
+    count BIGINT NOT NULL DEFAULT 1
+);
+
+CREATE INDEX IF NOT EXISTS idx_logs_service ON logs (service);
+CREATE INDEX IF NOT EXISTS idx_logs_level ON logs (level);
+CREATE INDEX IF NOT EXISTS idx_logs_timestamp ON logs (timestamp);
+CREATE INDEX IF NOT EXISTS idx_logs_trace_id ON logs (trace_id);
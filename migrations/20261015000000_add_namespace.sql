@@ -0,0 +1,4 @@
+-- NULL for entries ingested without a namespaced API key, read back as "default"
+ALTER TABLE logs ADD COLUMN IF NOT EXISTS namespace TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_logs_namespace ON logs (namespace);
@@ -0,0 +1,109 @@
+use chrono::{TimeDelta, Utc};
+use serde::Deserialize;
+use prometheus::IntCounterVec;
+use std::collections::HashMap;
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::filters::FilterParams;
+use crate::normalize_level;
+use crate::storage::SharedStorage;
+
+// Both the check period and the window the threshold applies to
+const ALERT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct AlertConfig {
+    pub(crate) name: String,
+    // Every service when unset
+    #[serde(default)]
+    pub(crate) service: Option<String>,
+    pub(crate) level: String,
+    pub(crate) threshold_per_minute: usize,
+    pub(crate) webhook_url: String,
+    // Minimum time between two firings of the same alert
+    #[serde(default = "default_cooldown_secs")]
+    pub(crate) cooldown_secs: u64,
+    // Delivery attempts in total, the delay doubling from retry_base_ms between them
+    #[serde(default = "default_max_retries")]
+    pub(crate) max_retries: usize,
+    #[serde(default = "default_retry_base_ms")]
+    pub(crate) retry_base_ms: u64,
+}
+
+fn default_cooldown_secs() -> u64 {
+    300
+}
+
+fn default_max_retries() -> usize {
+    3
+}
+
+fn default_retry_base_ms() -> u64 {
+    500
+}
+
+// Every minute, fire the webhook of each alert whose level saw more entries than its threshold
+pub(crate) async fn alert_monitor(storage: SharedStorage, alerts: Vec<AlertConfig>, webhook_failures: IntCounterVec) {
+    let client = reqwest::Client::new();
+    let mut last_fired: HashMap<String, Instant> = HashMap::new();
+
+    loop {
+        sleep(ALERT_WINDOW).await;
+
+        let since = Utc::now() - TimeDelta::from_std(ALERT_WINDOW).unwrap_or_default();
+        for alert in &alerts {
+            let cooldown = Duration::from_secs(alert.cooldown_secs);
+            if last_fired.get(&alert.name).is_some_and(|at| at.elapsed() < cooldown) {
+                continue;
+            }
+
+            let params = FilterParams {
+                service: alert.service.clone(),
+                level: Some(normalize_level(&alert.level).map_or_else(|| alert.level.clone(), str::to_string)),
+                since: Some(since.fixed_offset()),
+                ..Default::default()
+            };
+            let count = match storage.count(&params).await {
+                Ok(count) => count,
+                Err(e) => {
+                    tracing::error!(alert = %alert.name, error = %e, "alert cannot count logs");
+                    continue;
+                }
+            };
+            if count <= alert.threshold_per_minute {
+                continue;
+            }
+
+            last_fired.insert(alert.name.clone(), Instant::now());
+            let payload = serde_json::json!({
+                "alert": alert.name,
+                "count": count,
+                "threshold": alert.threshold_per_minute
+            });
+            // Retries back off for seconds, which must not delay the other alerts
+            tokio::spawn(deliver(client.clone(), alert.clone(), payload, webhook_failures.clone()));
+        }
+    }
+}
+
+async fn deliver(client: reqwest::Client, alert: AlertConfig, payload: serde_json::Value, webhook_failures: IntCounterVec) {
+    let attempts = alert.max_retries.max(1);
+    let mut delay = Duration::from_millis(alert.retry_base_ms);
+    let mut last_error = String::new();
+
+    for attempt in 1..=attempts {
+        match client.post(&alert.webhook_url).json(&payload).send().await {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) => last_error = format!("webhook returned {}", resp.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+        if attempt < attempts {
+            sleep(delay).await;
+            delay *= 2;
+        }
+    }
+
+    webhook_failures.with_label_values(&[&alert.name]).inc();
+    // No message: the record is {"alert_delivery_failed": <name>, "attempts": N, "last_error": "..."}
+    tracing::warn!(alert_delivery_failed = %alert.name, attempts, last_error);
+}
@@ -0,0 +1,94 @@
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use chrono::Utc;
+use serde::Deserialize;
+use std::path::Path;
+use tokio::time::{sleep, Duration};
+
+// Upload attempts per backup cycle, with the delay doubling from UPLOAD_RETRY_BASE
+const UPLOAD_ATTEMPTS: u32 = 3;
+const UPLOAD_RETRY_BASE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct S3BackupConfig {
+    pub(crate) bucket: String,
+    #[serde(default)]
+    pub(crate) prefix: String,
+    pub(crate) region: String,
+    pub(crate) access_key_id: String,
+    pub(crate) secret_access_key: String,
+    pub(crate) backup_interval_secs: u64,
+    // Overrides the AWS endpoint for S3-compatible stores such as MinIO
+    #[serde(default)]
+    pub(crate) endpoint_url: Option<String>,
+}
+
+fn client(cfg: &S3BackupConfig) -> Client {
+    let credentials = Credentials::new(&cfg.access_key_id, &cfg.secret_access_key, None, None, "config");
+    let mut config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new(cfg.region.clone()))
+        .credentials_provider(credentials);
+    if let Some(endpoint_url) = &cfg.endpoint_url {
+        config = config.endpoint_url(endpoint_url).force_path_style(true);
+    }
+    Client::from_conf(config.build())
+}
+
+// <prefix>/<date>/<filename>, so each day's uploads overwrite only that day's copy
+fn object_key(cfg: &S3BackupConfig, path: &str) -> String {
+    let filename = Path::new(path).file_name().map(|f| f.to_string_lossy()).unwrap_or_default();
+    let date = Utc::now().format("%Y-%m-%d");
+    let prefix = cfg.prefix.trim_matches('/');
+    if prefix.is_empty() {
+        format!("{}/{}", date, filename)
+    } else {
+        format!("{}/{}/{}", prefix, date, filename)
+    }
+}
+
+// Periodically upload the persisted log file; runs as its own task beside the server
+pub(crate) async fn run_s3_backup(cfg: S3BackupConfig, path: String) {
+    let client = client(&cfg);
+    let interval = Duration::from_secs(cfg.backup_interval_secs.max(1));
+
+    loop {
+        sleep(interval).await;
+
+        let body = match tokio::fs::read(&path).await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!(path, error = %e, "backup cannot read file");
+                continue;
+            }
+        };
+        let key = object_key(&cfg, &path);
+
+        let mut delay = UPLOAD_RETRY_BASE;
+        for attempt in 1..=UPLOAD_ATTEMPTS {
+            let result = client
+                .put_object()
+                .bucket(&cfg.bucket)
+                .key(&key)
+                .body(ByteStream::from(body.clone()))
+                .send()
+                .await;
+            match result {
+                Ok(_) => {
+                    tracing::info!(path, bucket = %cfg.bucket, key, "backup uploaded");
+                    break;
+                }
+                Err(e) => {
+                    tracing::warn!(path, attempt, max_attempts = UPLOAD_ATTEMPTS, error = %e, "backup upload failed");
+                    if attempt < UPLOAD_ATTEMPTS {
+                        sleep(delay).await;
+                        delay *= 2;
+                    } else {
+                        tracing::error!(path, "backup giving up on this cycle");
+                    }
+                }
+            }
+        }
+    }
+}
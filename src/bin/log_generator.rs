@@ -1,18 +1,209 @@
+use clap::Parser;
+use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
 use reqwest::Client;
-use serde_json::json;
+use serde_json::{json, Value};
 use chrono::Utc;
-use tokio::time::{sleep, Duration};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::time::{interval, Duration, Instant, MissedTickBehavior};
+
+/// Send a steady stream of synthetic logs to the log aggregator server
+#[derive(Parser, Debug)]
+#[command(name = "log_generator")]
+struct Args {
+    /// server base URL
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
+    server: String,
+
+    /// logs per second
+    #[arg(long, default_value_t = 1.0, value_parser = parse_rate, allow_negative_numbers = true)]
+    rate: f64,
+
+    /// comma-separated services to pick from
+    #[arg(long, value_delimiter = ',', default_value = "auth,payments,orders,inventory,shipping")]
+    services: Vec<String>,
+
+    /// comma-separated levels to pick from
+    #[arg(long, value_delimiter = ',', default_value = "INFO,WARN,ERROR")]
+    levels: Vec<String>,
+
+    /// stop after this many seconds, 0 runs forever
+    #[arg(long, default_value_t = 0)]
+    duration: u64,
+
+    /// entries sent concurrently in each burst, on top of the normal rate
+    #[arg(long, requires = "burst_every")]
+    burst_count: Option<usize>,
+
+    /// seconds between bursts
+    #[arg(long, requires = "burst_count", value_parser = clap::value_parser!(u64).range(1..))]
+    burst_every: Option<u64>,
+
+    /// replay the JSON log entries in this file, one per line, at --rate and then exit
+    #[arg(long, conflicts_with = "burst_count")]
+    replay_file: Option<PathBuf>,
+
+    /// keep the replayed entries' own timestamps instead of stamping them with the current time
+    #[arg(long, requires = "replay_file")]
+    preserve_timestamps: bool,
+
+    /// walk a Markov chain over (service, level) states instead of picking each independently;
+    /// FILE is JSON {"service:level": [["service:level", weight], ...]}, omit it for the built-in table
+    #[arg(long, value_name = "FILE", num_args = 0..=1, conflicts_with_all = ["replay_file", "services", "levels"])]
+    markov: Option<Option<PathBuf>>,
+}
+
+// Used by --markov without a FILE: services mostly stay in their current level, and errors in
+// payments spill over into orders and shipping, so ERROR volume arrives in correlated bursts
+const DEFAULT_MARKOV_TABLE: &str = r#"{
+    "auth:INFO": [["auth:INFO", 6], ["payments:INFO", 3], ["auth:WARN", 1]],
+    "auth:WARN": [["auth:WARN", 5], ["auth:ERROR", 2], ["auth:INFO", 3]],
+    "auth:ERROR": [["auth:ERROR", 7], ["auth:WARN", 2], ["auth:INFO", 1]],
+    "payments:INFO": [["payments:INFO", 5], ["orders:INFO", 4], ["payments:WARN", 1]],
+    "payments:WARN": [["payments:WARN", 4], ["payments:ERROR", 3], ["payments:INFO", 3]],
+    "payments:ERROR": [["payments:ERROR", 7], ["orders:ERROR", 2], ["payments:WARN", 1]],
+    "orders:INFO": [["orders:INFO", 4], ["inventory:INFO", 3], ["shipping:INFO", 2], ["orders:WARN", 1]],
+    "orders:WARN": [["orders:WARN", 4], ["orders:ERROR", 2], ["orders:INFO", 4]],
+    "orders:ERROR": [["orders:ERROR", 6], ["shipping:ERROR", 2], ["orders:WARN", 2]],
+    "inventory:INFO": [["inventory:INFO", 5], ["inventory:WARN", 1], ["orders:INFO", 2], ["shipping:INFO", 2]],
+    "inventory:WARN": [["inventory:WARN", 5], ["inventory:ERROR", 1], ["inventory:INFO", 4]],
+    "inventory:ERROR": [["inventory:ERROR", 6], ["orders:ERROR", 2], ["inventory:WARN", 2]],
+    "shipping:INFO": [["shipping:INFO", 5], ["auth:INFO", 4], ["shipping:WARN", 1]],
+    "shipping:WARN": [["shipping:WARN", 5], ["shipping:ERROR", 1], ["shipping:INFO", 4]],
+    "shipping:ERROR": [["shipping:ERROR", 6], ["shipping:WARN", 3], ["auth:INFO", 1]]
+}"#;
+
+// (service, level) states and, per state, the weighted choice of the state that follows it
+struct MarkovChain {
+    states: Vec<(String, String)>,
+    transitions: Vec<(Vec<usize>, WeightedIndex<f64>)>,
+}
+
+impl MarkovChain {
+    // Every state named as a next state needs a row of its own, so the walk never gets stuck
+    fn parse(json: &str) -> Result<Self, String> {
+        let table: HashMap<String, Vec<(String, f64)>> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let mut names: Vec<&String> = table.keys().collect();
+        names.sort();
+        if names.is_empty() {
+            return Err("transition table has no states".to_string());
+        }
+        let index: HashMap<&String, usize> = names.iter().enumerate().map(|(i, name)| (*name, i)).collect();
+
+        let mut states = Vec::with_capacity(names.len());
+        let mut transitions = Vec::with_capacity(names.len());
+        for name in &names {
+            let (service, level) = name
+                .rsplit_once(':')
+                .ok_or_else(|| format!("state {:?} is not \"service:level\"", name))?;
+            states.push((service.to_string(), level.to_string()));
+
+            let row = &table[*name];
+            let next = row
+                .iter()
+                .map(|(to, _)| {
+                    index.get(to).copied().ok_or_else(|| format!("{:?} leads to {:?}, which has no transitions", name, to))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let weights = WeightedIndex::new(row.iter().map(|(_, weight)| *weight))
+                .map_err(|e| format!("weights of {:?}: {}", name, e))?;
+            transitions.push((next, weights));
+        }
+        Ok(Self { states, transitions })
+    }
+
+    fn random_state(&self) -> usize {
+        rand::thread_rng().gen_range(0..self.states.len())
+    }
+
+    fn next_state(&self, current: usize) -> usize {
+        let (next, weights) = &self.transitions[current];
+        next[weights.sample(&mut rand::thread_rng())]
+    }
+}
+
+// How often replay reports how far it has got
+const REPLAY_PROGRESS_EVERY: usize = 1000;
+
+fn parse_rate(raw: &str) -> Result<f64, String> {
+    let rate: f64 = raw.parse().map_err(|e| format!("{}", e))?;
+    if rate.is_finite() && rate > 0.0 {
+        Ok(rate)
+    } else {
+        Err(format!("rate must be a positive number of logs per second, got {}", raw))
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    let mut args = Args::parse();
+    args.services.retain(|s| !s.is_empty());
+    args.levels.retain(|l| !l.is_empty());
+    if args.services.is_empty() || args.levels.is_empty() {
+        eprintln!("error: --services and --levels must each name at least one value");
+        std::process::exit(2);
+    }
+
     let client = Client::new();
-    let services = vec!["auth", "payments", "orders", "inventory", "shipping"];
-    let levels = vec!["INFO", "WARN", "ERROR"];
+    let url = format!("{}/v1/logs", args.server.trim_end_matches('/'));
+
+    if let Some(path) = &args.replay_file {
+        if let Err(err) = replay(&client, &url, path, args.rate, args.preserve_timestamps).await {
+            eprintln!("error: cannot read {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let (Some(count), Some(every)) = (args.burst_count, args.burst_every) {
+        tokio::spawn(send_bursts(client.clone(), url.clone(), args.services.clone(), count, every));
+    }
+
+    let chain = match &args.markov {
+        Some(path) => {
+            let table = match path {
+                Some(path) => std::fs::read_to_string(path).unwrap_or_else(|err| {
+                    eprintln!("error: cannot read {}: {}", path.display(), err);
+                    std::process::exit(1);
+                }),
+                None => DEFAULT_MARKOV_TABLE.to_string(),
+            };
+            Some(MarkovChain::parse(&table).unwrap_or_else(|err| {
+                eprintln!("error: invalid --markov table: {}", err);
+                std::process::exit(2);
+            }))
+        }
+        None => None,
+    };
+    let mut state = chain.as_ref().map(MarkovChain::random_state);
+
+    let services = &args.services;
+    let levels = &args.levels;
+
+    let deadline = (args.duration > 0).then(|| Instant::now() + Duration::from_secs(args.duration));
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / args.rate));
+    // A slow request delays the next send instead of triggering a catch-up flood
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     loop {
-        let service = services[rand::thread_rng().gen_range(0..services.len())];
-        let level = levels[rand::thread_rng().gen_range(0..levels.len())];
+        ticker.tick().await;
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            break;
+        }
+
+        let (service, level) = match (&chain, state) {
+            (Some(chain), Some(current)) => {
+                state = Some(chain.next_state(current));
+                let (service, level) = &chain.states[current];
+                (service.as_str(), level.as_str())
+            }
+            _ => (
+                services[rand::thread_rng().gen_range(0..services.len())].as_str(),
+                levels[rand::thread_rng().gen_range(0..levels.len())].as_str(),
+            ),
+        };
 
         let message = generate_message(service, level);
 
@@ -23,7 +214,7 @@ async fn main() {
             "message": message
         });
 
-        let res = client.post("http://127.0.0.1:8080/logs")
+        let res = client.post(&url)
             .json(&log_entry)
             .send()
             .await;
@@ -32,9 +223,84 @@ async fn main() {
             Ok(_) => println!("Log sent: {:?}", log_entry),
             Err(err) => eprintln!("Error sending log: {:?}", err),
         }
+    }
+}
+
+// Send each line of a captured log file once, throttled to `rate` per second
+async fn replay(client: &Client, url: &str, path: &Path, rate: f64, preserve_timestamps: bool) -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::fs::File::open(path).await?).lines();
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / rate));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let (mut line_no, mut sent, mut failed) = (0, 0, 0);
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut log_entry = match serde_json::from_str::<Value>(&line) {
+            Ok(Value::Object(entry)) => entry,
+            Ok(_) => {
+                eprintln!("Skipping line {}: not a JSON object", line_no);
+                continue;
+            }
+            Err(err) => {
+                eprintln!("Skipping line {}: {}", line_no, err);
+                continue;
+            }
+        };
+        if !preserve_timestamps {
+            log_entry.insert("timestamp".to_string(), json!(Utc::now().to_rfc3339()));
+        }
+
+        ticker.tick().await;
+        match client.post(url).json(&log_entry).send().await.and_then(|r| r.error_for_status()) {
+            Ok(_) => sent += 1,
+            Err(err) => {
+                failed += 1;
+                eprintln!("Error sending line {}: {:?}", line_no, err);
+            }
+        }
+
+        if (sent + failed) % REPLAY_PROGRESS_EVERY == 0 {
+            println!("Replayed {} entries ({} failed)", sent + failed, failed);
+        }
+    }
 
-        // Random delay between 1–2 seconds
-        sleep(Duration::from_millis(rand::thread_rng().gen_range(1000..2000))).await;
+    println!("Replay finished: {} sent, {} failed", sent, failed);
+    Ok(())
+}
+
+// Every `every` seconds, fire `count` ERROR entries at once to load-test the server
+async fn send_bursts(client: Client, url: String, services: Vec<String>, count: usize, every: u64) {
+    let mut ticker = interval(Duration::from_secs(every));
+    // The first tick is immediate; wait a full period before the first burst
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let sends: Vec<_> = (0..count)
+            .map(|_| {
+                let service = services[rand::thread_rng().gen_range(0..services.len())].clone();
+                let request = client.post(&url).json(&json!({
+                    "timestamp": Utc::now().to_rfc3339(),
+                    "service": service,
+                    "level": "ERROR",
+                    "message": "LOAD TEST BURST"
+                }));
+                tokio::spawn(async move { request.send().await.and_then(|r| r.error_for_status()).is_ok() })
+            })
+            .collect();
+
+        let mut sent = 0;
+        for send in sends {
+            if send.await.unwrap_or(false) {
+                sent += 1;
+            }
+        }
+        println!("Burst sent: {}/{} accepted", sent, count);
     }
 }
 
@@ -68,6 +334,7 @@ fn generate_message(service: &str, level: &str) -> String {
         ("shipping", "WARN") => "Shipping delayed due to weather.".to_string(),
         ("shipping", "ERROR") => "Shipping service failed!".to_string(),
 
-        _ => "Unknown log message.".to_string(),
+        // Services and levels passed on the command line
+        _ => format!("{} event in {} service.", level, service),
     }
 }
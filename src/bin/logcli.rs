@@ -1,17 +1,33 @@
-use clap::Parser;
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use clap_complete::Shell;
+use colored::{ColoredString, Colorize};
+use config::{Config, FileFormat};
 use reqwest::blocking::Client;
+use serde::Deserialize;
 use serde_json::Value;
 use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::Duration;
 
 /// Simple CLI to query the log aggregator server
 #[derive(Parser, Debug)]
 #[command(name = "logcli")]
 #[command(about = "Query log aggregator", long_about = None)]
+#[command(after_help = COMPLETIONS_HELP)]
 struct Args {
     /// server base URL, e.g., http://127.0.0.1:8080
-    #[arg(short, long, default_value = "http://127.0.0.1:8080")]
+    // No short form, -s belongs to --service
+    #[arg(long, default_value = "http://127.0.0.1:8080")]
     server: String,
 
+    /// API version to prefix every request path with, e.g. v1; without it the server redirects
+    #[arg(long, value_name = "VERSION")]
+    api_version: Option<String>,
+
     /// filter by service name
     #[arg(short, long)]
     service: Option<String>,
@@ -20,53 +36,473 @@ struct Args {
     #[arg(short, long)]
     level: Option<String>,
 
+    /// only logs at least this severe: DEBUG < INFO < WARN < ERROR < CRITICAL
+    #[arg(long, value_name = "LEVEL")]
+    min_level: Option<String>,
+
+    /// only logs whose message contains this text (case-insensitive)
+    // -q after the server's parameter, -s belongs to --service
+    #[arg(short = 'q', long)]
+    search: Option<String>,
+
+    /// treat --search as a regular expression; an invalid pattern is rejected by the server with a 400
+    #[arg(long, requires = "search")]
+    regex: bool,
+
+    /// only logs at or after this time: RFC 3339 or a relative age like 5m, 1h, 2d
+    #[arg(long, value_parser = parse_duration_or_rfc3339)]
+    since: Option<String>,
+
+    /// only logs at or before this time: RFC 3339 or a relative age like 5m, 1h, 2d
+    #[arg(long, value_parser = parse_duration_or_rfc3339)]
+    until: Option<String>,
+
+    /// maximum number of logs per request
+    #[arg(long, default_value_t = DEFAULT_LIMIT)]
+    limit: usize,
+
+    /// number of matching logs to skip
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+
+    /// keep fetching pages until every matching log has been printed
+    #[arg(long)]
+    all: bool,
+
+    /// show only the newest N matching logs, newest first
+    #[arg(long, value_name = "N", conflicts_with_all = ["stats", "follow", "limit", "offset", "all"])]
+    tail: Option<usize>,
+
+    /// print only the number of matching logs
+    #[arg(long, conflicts_with_all = ["stats", "tail", "follow"])]
+    count: bool,
+
     /// show stats instead of logs
     #[arg(long)]
     stats: bool,
+
+    /// keep printing new logs as they arrive, until Ctrl+C
+    #[arg(short, long, conflicts_with = "stats")]
+    follow: bool,
+
+    /// disable coloured levels (also honours the NO_COLOR environment variable)
+    #[arg(long)]
+    no_color: bool,
+
+    /// print a completion script for this shell and exit
+    #[arg(long, value_name = "SHELL")]
+    completions: Option<Shell>,
+
+    /// output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// report which config file was read
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+// Defaults read from ~/.logclirc (or $LOGCLI_CONFIG), keyed by flag long name
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct RcFile {
+    server: Option<String>,
+    api_version: Option<String>,
+    service: Option<String>,
+    level: Option<String>,
+    min_level: Option<String>,
+    search: Option<String>,
+    regex: Option<bool>,
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<usize>,
+    no_color: Option<bool>,
+    output: Option<OutputFormat>,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum OutputFormat {
+    /// one readable line per entry
+    Text,
+    /// the server response, pretty-printed
+    Json,
+    /// one JSON object per line
+    Ndjson,
+    /// timestamp,service,level,message rows under a header
+    Csv,
+}
+
+const COMPLETIONS_HELP: &str = "\
+Installing completions:
+  bash:        logcli --completions bash > ~/.local/share/bash-completion/completions/logcli
+  zsh:         logcli --completions zsh > ~/.zfunc/_logcli  (with ~/.zfunc in $fpath)
+  fish:        logcli --completions fish > ~/.config/fish/completions/logcli.fish
+  powershell:  logcli --completions powershell >> $PROFILE";
+
+const DEFAULT_LIMIT: usize = 100;
+
+const CSV_HEADER: &str = "timestamp,service,level,message";
+
+fn rc_path() -> Option<PathBuf> {
+    match std::env::var_os("LOGCLI_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".logclirc")),
+    }
+}
+
+// A missing file yields no defaults; a malformed one is an error
+fn load_rc(path: &Path) -> Result<RcFile, Box<dyn Error>> {
+    if !path.is_file() {
+        return Ok(RcFile::default());
+    }
+    let settings = Config::builder()
+        .add_source(config::File::from(path).format(FileFormat::Toml))
+        .build()?;
+    settings
+        .try_deserialize()
+        .map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+// Fill in every flag the command line left unset from the rc file
+fn apply_rc(args: &mut Args, matches: &ArgMatches, rc: RcFile) -> Result<(), Box<dyn Error>> {
+    let unset = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+    if let Some(server) = rc.server.filter(|_| unset("server")) {
+        args.server = server;
+    }
+    if let Some(api_version) = rc.api_version.filter(|_| unset("api_version")) {
+        args.api_version = Some(api_version);
+    }
+    if let Some(service) = rc.service.filter(|_| unset("service")) {
+        args.service = Some(service);
+    }
+    if let Some(level) = rc.level.filter(|_| unset("level")) {
+        args.level = Some(level);
+    }
+    if let Some(min_level) = rc.min_level.filter(|_| unset("min_level")) {
+        args.min_level = Some(min_level);
+    }
+    if let Some(search) = rc.search.filter(|_| unset("search")) {
+        args.search = Some(search);
+    }
+    if let Some(regex) = rc.regex.filter(|_| unset("regex")) {
+        args.regex = regex;
+    }
+    if let Some(since) = rc.since.filter(|_| unset("since")) {
+        args.since = Some(parse_duration_or_rfc3339(&since).map_err(|e| format!("since: {}", e))?);
+    }
+    if let Some(until) = rc.until.filter(|_| unset("until")) {
+        args.until = Some(parse_duration_or_rfc3339(&until).map_err(|e| format!("until: {}", e))?);
+    }
+    if let Some(limit) = rc.limit.filter(|_| unset("limit")) {
+        args.limit = limit;
+    }
+    if let Some(no_color) = rc.no_color.filter(|_| unset("no_color")) {
+        args.no_color = no_color;
+    }
+    if let Some(output) = rc.output.filter(|_| unset("output")) {
+        args.output = output;
+    }
+    Ok(())
+}
+
+// Delay before reconnecting a dropped --follow stream
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+// --server plus the --api-version prefix, for appending a request path to
+fn base_url(args: &Args) -> String {
+    let server = args.server.trim_end_matches('/');
+    match args.api_version.as_deref().map(|v| v.trim_matches('/')) {
+        Some(version) if !version.is_empty() => format!("{}/{}", server, version),
+        _ => server.to_string(),
+    }
+}
+
+// Query parameters shared by every log-selecting request
+fn filter_query(args: &Args) -> String {
+    let mut q = vec![];
+    if let Some(s) = &args.service {
+        q.push(format!("service={}", urlencoding::encode(s)));
+    }
+    if let Some(lv) = &args.level {
+        q.push(format!("level={}", urlencoding::encode(lv)));
+    }
+    if let Some(min_level) = &args.min_level {
+        q.push(format!("min_level={}", urlencoding::encode(min_level)));
+    }
+    if let Some(search) = &args.search {
+        let key = if args.regex { "q_regex" } else { "q" };
+        q.push(format!("{}={}", key, urlencoding::encode(search)));
+    }
+    if let Some(since) = &args.since {
+        q.push(format!("since={}", urlencoding::encode(since)));
+    }
+    if let Some(until) = &args.until {
+        q.push(format!("until={}", urlencoding::encode(until)));
+    }
+    if q.is_empty() {
+        String::new()
+    } else {
+        format!("?{}", q.join("&"))
+    }
+}
+
+// Accept an RFC 3339 timestamp as-is, or turn "<n><s|m|h|d>" into that long before now
+fn parse_duration_or_rfc3339(raw: &str) -> Result<String, String> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(ts.to_rfc3339());
+    }
+
+    let unrecognised = || format!("expected an RFC 3339 timestamp or a duration like 30s, 5m, 1h, 2d, got {:?}", raw);
+    let split = raw.find(|c: char| !c.is_ascii_digit()).ok_or_else(unrecognised)?;
+    let (amount, unit) = raw.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| unrecognised())?;
+    let age = match unit {
+        "s" => TimeDelta::try_seconds(amount),
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        _ => None,
+    }
+    .ok_or_else(unrecognised)?;
+
+    Ok((Utc::now() - age).to_rfc3339())
+}
+
+fn print_entry(entry: &Value, output: OutputFormat) {
+    let field = |name: &str| entry.get(name).and_then(|v| v.as_str()).unwrap_or("-");
+
+    match output {
+        OutputFormat::Text => println!(
+            "[{}] {} / {} -> {}",
+            field("timestamp"),
+            field("service"),
+            colour_level(field("level")),
+            field("message")
+        ),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(entry).unwrap_or_default()),
+        OutputFormat::Ndjson => println!("{}", entry),
+        OutputFormat::Csv => {
+            let row: Vec<String> = ["timestamp", "service", "level", "message"]
+                .into_iter()
+                .map(|name| csv_field(field(name)))
+                .collect();
+            println!("{}", row.join(","));
+        }
+    }
+}
+
+// Quote fields containing a separator, quote or line break, doubling embedded quotes
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Flatten nested objects into dotted key,value rows
+fn print_csv_pairs(prefix: &str, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                print_csv_pairs(&key, value);
+            }
+        }
+        Value::String(s) => println!("{},{}", csv_field(prefix), csv_field(s)),
+        other => println!("{},{}", csv_field(prefix), csv_field(&other.to_string())),
+    }
+}
+
+fn colour_level(level: &str) -> ColoredString {
+    match level {
+        "DEBUG" => level.cyan(),
+        "INFO" => level.green(),
+        "WARN" => level.yellow(),
+        "ERROR" => level.red(),
+        "CRITICAL" => level.bright_red(),
+        _ => level.normal(),
+    }
+}
+
+// Tail /logs/stream forever; Ctrl+C ends the process like any other foreground command
+fn follow(args: &Args) -> Result<(), Box<dyn Error>> {
+    // No overall timeout, the stream is meant to stay open
+    let client = Client::builder().timeout(None).build()?;
+    let url = format!("{}/logs/stream{}", base_url(args), filter_query(args));
+    let mut last_id: Option<String> = None;
+
+    if args.output == OutputFormat::Csv {
+        println!("{}", CSV_HEADER);
+    }
+
+    loop {
+        let mut req = client.get(&url);
+        // Resume where the previous connection stopped
+        if let Some(id) = &last_id {
+            req = req.header("Last-Event-ID", id);
+        }
+
+        match req.send().and_then(|resp| resp.error_for_status()) {
+            Ok(resp) => {
+                let mut event = String::new();
+                for line in BufReader::new(resp).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if let Some(name) = line.strip_prefix("event:") {
+                        event = name.trim().to_string();
+                    } else if let Some(id) = line.strip_prefix("id:") {
+                        last_id = Some(id.trim().to_string());
+                    } else if let Some(data) = line.strip_prefix("data:") {
+                        if event == "lag" {
+                            eprintln!("warning: fell behind the stream, some logs were skipped: {}", data.trim());
+                        } else if let Ok(entry) = serde_json::from_str::<Value>(data.trim()) {
+                            print_entry(&entry, args.output);
+                        }
+                    } else if line.is_empty() {
+                        event.clear();
+                    }
+                }
+            }
+            Err(e) => eprintln!("error: {}", e),
+        }
+
+        eprintln!("reconnecting...");
+        sleep(RECONNECT_DELAY);
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Some(shell) = args.completions {
+        clap_complete::generate(shell, &mut Args::command(), "logcli", &mut std::io::stdout());
+        return Ok(());
+    }
+
+    if let Some(path) = rc_path() {
+        if args.verbose {
+            let state = if path.is_file() { "" } else { " (not found)" };
+            eprintln!("config: {}{}", path.display(), state);
+        }
+        apply_rc(&mut args, &matches, load_rc(&path)?)?;
+    }
+
     let client = Client::new();
 
+    if args.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        colored::control::set_override(false);
+    }
+
+    if args.follow {
+        return follow(&args);
+    }
+
     if args.stats {
-        let url = format!("{}/logs/stats", args.server.trim_end_matches('/'));
+        let url = format!("{}/logs/stats", base_url(&args));
         let resp = client.get(&url).send()?;
         let json: Value = resp.json()?;
-        println!("{}", serde_json::to_string_pretty(&json)?);
+        match args.output {
+            OutputFormat::Text | OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&json)?),
+            OutputFormat::Ndjson => println!("{}", json),
+            OutputFormat::Csv => {
+                println!("key,value");
+                print_csv_pairs("", &json);
+            }
+        }
         return Ok(());
     }
 
-    // Build /logs?params
-    let mut url = format!("{}/logs", args.server.trim_end_matches('/'));
-    let mut q = vec![];
-    if let Some(s) = args.service {
-        q.push(format!("service={}", urlencoding::encode(&s)));
-    }
-    if let Some(lv) = args.level {
-        q.push(format!("level={}", urlencoding::encode(&lv)));
+    if args.count {
+        let url = format!("{}/logs/count{}", base_url(&args), filter_query(&args));
+        let json: Value = client.get(&url).send()?.json()?;
+        // Bare integer on stdout so the result can be captured by a shell
+        match json.get("count").and_then(|v| v.as_u64()) {
+            Some(count) => println!("{}", count),
+            None => return Err(format!("unexpected response: {}", json).into()),
+        }
+        return Ok(());
     }
-    if !q.is_empty() {
-        url.push('?');
-        url.push_str(&q.join("&"));
+
+    if let Some(n) = args.tail {
+        let query = filter_query(&args);
+        let url = format!(
+            "{}/logs/tail{}{}n={}",
+            base_url(&args),
+            query,
+            if query.is_empty() { '?' } else { '&' },
+            n
+        );
+        let json: Value = client.get(&url).send()?.json()?;
+
+        match json.as_array() {
+            Some(arr) if args.output != OutputFormat::Json => {
+                if args.output == OutputFormat::Csv {
+                    println!("{}", CSV_HEADER);
+                }
+                for entry in arr {
+                    print_entry(entry, args.output);
+                }
+            }
+            // JSON output, or an error body
+            _ => println!("{}", serde_json::to_string_pretty(&json)?),
+        }
+        return Ok(());
     }
 
-    let resp = client.get(&url).send()?;
-    let json: Value = resp.json()?;
+    let limit = if args.limit == 0 {
+        eprintln!("warning: --limit 0 would fetch nothing, using {}", DEFAULT_LIMIT);
+        DEFAULT_LIMIT
+    } else {
+        args.limit
+    };
+
+    // Build /logs?params
+    let query = filter_query(&args);
+    let base = format!("{}/logs{}{}", base_url(&args), query, if query.is_empty() { '?' } else { '&' });
+
+    let mut offset = args.offset;
+    let mut printed_header = false;
+    loop {
+        let url = format!("{}limit={}&offset={}", base, limit, offset);
+        let json: Value = client.get(&url).send()?.json()?;
 
-    // pretty print each log in a readable format
-    if let Some(arr) = json.as_array() {
-        for entry in arr {
-            // Expecting fields: timestamp, service, level, message
-            let ts = entry.get("timestamp").and_then(|v| v.as_str()).unwrap_or("-");
-            let svc = entry.get("service").and_then(|v| v.as_str()).unwrap_or("-");
-            let level = entry.get("level").and_then(|v| v.as_str()).unwrap_or("-");
-            let msg = entry.get("message").and_then(|v| v.as_str()).unwrap_or("-");
+        let Some(arr) = json.get("logs").and_then(|v| v.as_array()) else {
+            println!("{}", serde_json::to_string_pretty(&json)?);
+            break;
+        };
+        let total = json.get("total").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
 
-            println!("[{}] {} / {} -> {}", ts, svc, level, msg);
+        if args.output == OutputFormat::Json {
+            println!("{}", serde_json::to_string_pretty(&json)?);
+        } else {
+            // Only the first page gets a header; with --all the rest follow seamlessly
+            if !printed_header {
+                let range = format!("# showing {}..{} of {}", offset, offset + arr.len(), total);
+                // Keep machine-readable stdout clean
+                if args.output == OutputFormat::Text {
+                    println!("{}", range);
+                } else {
+                    eprintln!("{}", range);
+                }
+                if args.output == OutputFormat::Csv {
+                    println!("{}", CSV_HEADER);
+                }
+                printed_header = true;
+            }
+            for entry in arr {
+                print_entry(entry, args.output);
+            }
+        }
+
+        offset += arr.len();
+        if !args.all || arr.is_empty() || offset >= total {
+            break;
         }
-    } else {
-        println!("{}", serde_json::to_string_pretty(&json)?);
     }
 
     Ok(())
@@ -0,0 +1,90 @@
+use prometheus::{CounterVec, IntCounter};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::ingest::IngestContext;
+use crate::kafka::KafkaForwarder;
+use crate::{ingest_many, Broadcaster, LogEntry, LogEvent};
+
+// Entries written to storage together when the queue has backed up
+const DISPATCH_BATCH: usize = 256;
+
+// Prepared entries from POST /logs on their way to the dispatcher
+pub(crate) type LogBus = mpsc::Sender<LogEntry>;
+
+// Something told about every ingested entry; storage itself always comes first
+pub(crate) trait Sink: Send + Sync {
+    // Every entry accepted for ingestion, before duplicates are merged
+    fn received(&self, _entry: &LogEntry) {}
+
+    // Every entry once stored, updated when deduplication grew the count of an existing one
+    fn stored(&self, _entry: &LogEntry, _payload: &str, _updated: bool) {}
+}
+
+// The sinks every ingestion path fans out to, in order
+#[derive(Clone)]
+pub(crate) struct Sinks(Arc<Vec<Box<dyn Sink>>>);
+
+impl Sinks {
+    pub(crate) fn new(sinks: Vec<Box<dyn Sink>>) -> Self {
+        Self(Arc::new(sinks))
+    }
+
+    pub(crate) fn received(&self, entry: &LogEntry) {
+        for sink in self.0.iter() {
+            sink.received(entry);
+        }
+    }
+
+    // Serialised once here and shared by every sink
+    pub(crate) fn stored(&self, entry: &LogEntry, updated: bool) {
+        let Ok(payload) = serde_json::to_string(entry) else {
+            return;
+        };
+        for sink in self.0.iter() {
+            sink.stored(entry, &payload, updated);
+        }
+    }
+}
+
+pub(crate) struct MetricsSink {
+    pub(crate) total_logs: IntCounter,
+    pub(crate) logs_ingested: CounterVec,
+}
+
+impl Sink for MetricsSink {
+    fn received(&self, entry: &LogEntry) {
+        self.total_logs.inc();
+        self.logs_ingested.with_label_values(&[&entry.service, &entry.level]).inc();
+    }
+}
+
+// SSE and WebSocket subscribers
+pub(crate) struct BroadcastSink(pub(crate) Broadcaster);
+
+impl Sink for BroadcastSink {
+    fn stored(&self, _entry: &LogEntry, payload: &str, updated: bool) {
+        let _ = self.0.send(LogEvent { payload: payload.to_string(), updated });
+    }
+}
+
+impl Sink for KafkaForwarder {
+    fn stored(&self, entry: &LogEntry, payload: &str, _updated: bool) {
+        self.forward(&entry.service, payload);
+    }
+}
+
+// Store whatever POST /logs queued, then fan out; the alert monitor reads storage, so it sees
+// entries as soon as they are written. Returns once every sender is gone
+pub(crate) async fn dispatcher(mut rx: mpsc::Receiver<LogEntry>, ctx: IngestContext) {
+    let mut batch = Vec::with_capacity(DISPATCH_BATCH);
+    while rx.recv_many(&mut batch, DISPATCH_BATCH).await > 0 {
+        let entries = std::mem::take(&mut batch);
+        let len = entries.len();
+        let span = tracing::trace_span!("dispatch", entries = len);
+        if let Err(e) = ingest_many(&ctx.storage, &ctx.seq, &ctx.dedup, &ctx.sinks, entries).instrument(span).await {
+            tracing::error!(entries = len, error = %e, "dispatcher dropping entries");
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use actix_cors::Cors;
+use actix_web::http::Uri;
+use serde::Deserialize;
+
+use crate::middleware::REQUEST_ID_HEADER;
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct CorsConfig {
+    // Exact origins such as "https://dash.example.com", or "*" for any
+    pub(crate) allowed_origins: Vec<String>,
+    #[serde(default)]
+    pub(crate) allow_credentials: bool,
+    // How long browsers may cache a preflight answer
+    #[serde(default = "default_max_age_secs")]
+    pub(crate) max_age_secs: u64,
+}
+
+fn default_max_age_secs() -> u64 {
+    3600
+}
+
+impl CorsConfig {
+    // actix-cors only reports a bad origin when a worker starts, so check them up front
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        for origin in self.allowed_origins.iter().filter(|o| *o != "*") {
+            origin.parse::<Uri>().map_err(|e| format!("invalid cors origin {:?}: {}", origin, e))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*" || o == origin)
+    }
+
+    pub(crate) fn middleware(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allow_any_method()
+            .allow_any_header()
+            .expose_headers([REQUEST_ID_HEADER])
+            .max_age(self.max_age_secs as usize);
+        if self.allowed_origins.iter().any(|o| o == "*") {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &self.allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
+        if self.allow_credentials {
+            cors = cors.supports_credentials();
+        }
+        cors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::CorsPreflightMiddleware;
+    use actix_web::http::{header, Method, StatusCode};
+    use actix_web::{test, web, App, HttpResponse};
+
+    const DASHBOARD: &str = "https://dash.example.com";
+
+    fn config(origins: &[&str], allow_credentials: bool) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            allow_credentials,
+            max_age_secs: 600,
+        }
+    }
+
+    // Wrapped the way main wraps the API
+    async fn call(cfg: &CorsConfig, req: test::TestRequest) -> actix_web::dev::ServiceResponse {
+        let app = test::init_service(
+            App::new()
+                .wrap(cfg.middleware())
+                .wrap(CorsPreflightMiddleware::new(Some(cfg)))
+                .route("/v1/logs", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        test::call_service(&app, req.to_request()).await.map_into_boxed_body()
+    }
+
+    fn header_value(resp: &actix_web::dev::ServiceResponse, name: header::HeaderName) -> Option<&str> {
+        resp.headers().get(name).map(|v| v.to_str().unwrap())
+    }
+
+    fn preflight(origin: &str) -> test::TestRequest {
+        test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/v1/logs")
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+    }
+
+    #[actix_web::test]
+    async fn allowed_origin_gets_cors_headers() {
+        let cfg = config(&[DASHBOARD], true);
+        let req = test::TestRequest::get().uri("/v1/logs").insert_header((header::ORIGIN, DASHBOARD));
+        let resp = call(&cfg, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some(DASHBOARD));
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_CREDENTIALS), Some("true"));
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_EXPOSE_HEADERS), Some(REQUEST_ID_HEADER));
+    }
+
+    #[actix_web::test]
+    async fn preflight_from_allowed_origin() {
+        let resp = call(&config(&[DASHBOARD], false), preflight(DASHBOARD)).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some(DASHBOARD));
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_MAX_AGE), Some("600"));
+        assert!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_METHODS).is_some_and(|m| m.contains("GET")));
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_CREDENTIALS), None);
+    }
+
+    #[actix_web::test]
+    async fn preflight_from_other_origin_is_forbidden() {
+        let resp = call(&config(&[DASHBOARD], false), preflight("https://evil.example.com")).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+    }
+
+    #[actix_web::test]
+    async fn wildcard_allows_any_origin() {
+        let cfg = config(&["*"], false);
+        let resp = call(&cfg, preflight("https://anywhere.example.com")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/v1/logs").insert_header((header::ORIGIN, "https://anywhere.example.com"));
+        let resp = call(&cfg, req).await;
+        assert!(header_value(&resp, header::ACCESS_CONTROL_ALLOW_ORIGIN).is_some());
+    }
+
+    #[actix_web::test]
+    async fn validate_rejects_malformed_origins() {
+        assert!(config(&["*", DASHBOARD], false).validate().is_ok());
+        assert!(config(&["not an origin"], false).validate().is_err());
+    }
+}
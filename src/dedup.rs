@@ -0,0 +1,96 @@
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::storage::{SharedStorage, StorageError};
+use crate::namespace::namespace_of;
+use crate::LogEntry;
+
+pub(crate) type Fingerprint = [u8; 32];
+
+// sha256 over namespace, service, level and message, NUL separated so fields cannot run into each other
+pub(crate) fn fingerprint(entry: &LogEntry) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    hasher.update(namespace_of(entry).as_bytes());
+    hasher.update([0]);
+    hasher.update(entry.service.as_bytes());
+    hasher.update([0]);
+    hasher.update(entry.level.as_bytes());
+    hasher.update([0]);
+    hasher.update(entry.message.as_bytes());
+    hasher.finalize().into()
+}
+
+// Remembers which stored entry each fingerprint was merged into, forgetting the oldest past max_entries
+#[derive(Debug)]
+pub(crate) struct Deduplicator {
+    enabled: bool,
+    max_entries: usize,
+    state: Mutex<DedupState>,
+}
+
+#[derive(Debug, Default)]
+struct DedupState {
+    ids: HashMap<Fingerprint, u64>,
+    order: VecDeque<Fingerprint>,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(enabled: bool, max_entries: usize) -> Self {
+        Self { enabled, max_entries, state: Mutex::default() }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(crate) fn record(&self, fp: Fingerprint, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.ids.insert(fp, id).is_none() {
+            state.order.push_back(fp);
+        }
+        while state.ids.len() > self.max_entries {
+            let Some(oldest) = state.order.pop_front() else {
+                break;
+            };
+            state.ids.remove(&oldest);
+        }
+    }
+
+    // Forget every mapping, needed once ids are reused after DELETE /logs resets the sequence
+    pub(crate) fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.ids.clear();
+        state.order.clear();
+    }
+
+    // Forget the fingerprint merged into a deleted entry
+    pub(crate) fn forget_id(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        let Some(fp) = state.ids.iter().find(|(_, mapped)| **mapped == id).map(|(fp, _)| *fp) else {
+            return;
+        };
+        state.ids.remove(&fp);
+        state.order.retain(|f| *f != fp);
+    }
+
+    // Add count to the stored entry with this fingerprint, returning it as updated
+    pub(crate) async fn merge(
+        &self,
+        storage: &SharedStorage,
+        fp: Fingerprint,
+        count: usize,
+    ) -> Result<Option<LogEntry>, StorageError> {
+        let Some(id) = self.state.lock().unwrap().ids.get(&fp).copied() else {
+            return Ok(None);
+        };
+        let updated = storage.increment_count(id, count).await?;
+        if updated.is_none() {
+            // The entry was evicted or deleted since; the next duplicate starts a new one
+            let mut state = self.state.lock().unwrap();
+            state.ids.remove(&fp);
+            state.order.retain(|f| *f != fp);
+        }
+        Ok(updated)
+    }
+}
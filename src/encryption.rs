@@ -0,0 +1,57 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::borrow::Cow;
+
+const NONCE_LEN: usize = 12;
+
+// Seals persisted lines as base64(nonce || AES-256-GCM ciphertext) when [logging] encryption_key_hex is set
+#[derive(Clone)]
+pub(crate) struct LineCipher(Aes256Gcm);
+
+impl LineCipher {
+    pub(crate) fn from_hex(key_hex: &str) -> Result<Self, String> {
+        let key = hex::decode(key_hex.trim()).map_err(|e| format!("encryption_key_hex is not hex: {}", e))?;
+        let key: [u8; 32] = key.try_into().map_err(|key: Vec<u8>| {
+            format!("encryption_key_hex must be 32 bytes (64 hex characters), got {} bytes", key.len())
+        })?;
+        Ok(Self(Aes256Gcm::new(&key.into())))
+    }
+
+    // A fresh random nonce per line, so equal entries never produce equal lines
+    pub(crate) fn seal(&self, line: &str) -> Result<String, String> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.0.encrypt(&nonce, line.as_bytes()).map_err(|e| format!("encryption failed: {}", e))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    pub(crate) fn open(&self, line: &str) -> Result<String, String> {
+        let sealed = STANDARD.decode(line.trim()).map_err(|e| format!("not base64: {}", e))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(format!("shorter than the {}-byte nonce", NONCE_LEN));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        // GCM cannot tell a wrong key from a modified line
+        let plaintext = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "authentication failed, wrong encryption_key_hex or a corrupted line".to_string())?;
+        String::from_utf8(plaintext).map_err(|e| format!("decrypted to invalid UTF-8: {}", e))
+    }
+}
+
+// Whether a persisted line is still plain JSON, written before encryption was enabled
+pub(crate) fn is_plain(line: &str) -> bool {
+    line.trim_start().starts_with('{')
+}
+
+// The JSON text of a persisted line, decrypting it unless it is plain
+pub(crate) fn decode_line<'a>(cipher: Option<&LineCipher>, line: &'a str) -> Result<Cow<'a, str>, String> {
+    match cipher {
+        Some(cipher) if !is_plain(line) => cipher.open(line).map(Cow::Owned),
+        _ => Ok(Cow::Borrowed(line)),
+    }
+}
@@ -0,0 +1,73 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::LogEntry;
+
+// Where POST /logs finds the core fields of an entry, for clients with their own schema
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct FieldMapping {
+    #[serde(default = "default_timestamp_field")]
+    pub(crate) timestamp_field: String,
+    #[serde(default = "default_service_field")]
+    pub(crate) service_field: String,
+    #[serde(default = "default_level_field")]
+    pub(crate) level_field: String,
+    #[serde(default = "default_message_field")]
+    pub(crate) message_field: String,
+}
+
+fn default_timestamp_field() -> String {
+    "timestamp".to_string()
+}
+
+fn default_service_field() -> String {
+    "service".to_string()
+}
+
+fn default_level_field() -> String {
+    "level".to_string()
+}
+
+fn default_message_field() -> String {
+    "message".to_string()
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            timestamp_field: default_timestamp_field(),
+            service_field: default_service_field(),
+            level_field: default_level_field(),
+            message_field: default_message_field(),
+        }
+    }
+}
+
+impl FieldMapping {
+    // Move each mapped field to its LogEntry name; the other LogEntry fields keep their own names
+    // and anything else in the object is ignored
+    pub(crate) fn apply(&self, raw: Value) -> Result<LogEntry, String> {
+        let Value::Object(mut object) = raw else {
+            return Err("expected a JSON object".to_string());
+        };
+
+        let fields = [
+            (&self.timestamp_field, "timestamp"),
+            (&self.service_field, "service"),
+            (&self.level_field, "level"),
+            (&self.message_field, "message"),
+        ];
+        let mut mapped = Vec::with_capacity(fields.len());
+        for (source, target) in fields {
+            match object.remove(source.as_str()) {
+                Some(value) => mapped.push((target, value)),
+                None => return Err(format!("missing field `{}`", source)),
+            }
+        }
+        for (target, value) in mapped {
+            object.insert(target.to_string(), value);
+        }
+
+        serde_json::from_value(Value::Object(object)).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,351 @@
+use actix_web::HttpResponse;
+use chrono::{DateTime, FixedOffset};
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use tokio::time::Duration;
+use utoipa::IntoParams;
+
+use crate::namespace::{namespace_of, Namespace};
+use crate::LogEntry;
+
+// Upper bound on the length of a message search query, in bytes
+pub(crate) const MAX_QUERY_LEN: usize = 256;
+
+// Limits applied when compiling q_regex patterns
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+const REGEX_COMPILE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Filters shared by every endpoint that selects a subset of the store
+#[derive(Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FilterParams {
+    pub(crate) service: Option<String>,
+    pub(crate) level: Option<String>,
+    // Severity of min_level=<level>; only entries at least this severe match
+    #[param(rename = "min_level", value_type = Option<String>)]
+    pub(crate) min_severity: Option<u8>,
+    pub(crate) trace_id: Option<String>,
+    // Lowercased substring matched against the message
+    #[param(max_length = 256)]
+    pub(crate) q: Option<String>,
+    #[param(value_type = Option<String>, max_length = 256)]
+    pub(crate) q_regex: Option<Regex>,
+    #[param(value_type = Option<String>, format = DateTime)]
+    pub(crate) since: Option<DateTime<FixedOffset>>,
+    #[param(value_type = Option<String>, format = DateTime)]
+    pub(crate) until: Option<DateTime<FixedOffset>>,
+    // meta.<key>=<value> pairs, compared against the value's string form; each handler lists them
+    // in the spec, since the parameter names are open-ended
+    #[param(ignore)]
+    pub(crate) metadata: Vec<(String, String)>,
+    // Set from the caller's API key rather than the query; "default" also matches entries without one
+    #[param(ignore)]
+    pub(crate) namespace: Option<String>,
+}
+
+impl FilterParams {
+    // Build from query parameters, scoped to the caller's namespace, producing a 400 response for
+    // anything malformed
+    pub(crate) async fn from_query(query: &HashMap<String, String>, namespace: &Namespace) -> Result<Self, HttpResponse> {
+        if query.contains_key("q") && query.contains_key("q_regex") {
+            return Err(bad_request("q and q_regex are mutually exclusive"));
+        }
+
+        let q_regex = match query.get("q_regex") {
+            Some(pattern) => Some(compile_query_regex(pattern).await.map_err(bad_request)?),
+            None => None,
+        };
+
+        let q = match query.get("q") {
+            Some(q) if q.len() > MAX_QUERY_LEN => {
+                return Err(bad_request(format!("q must be at most {} bytes", MAX_QUERY_LEN)));
+            }
+            Some(q) => Some(q.to_lowercase()),
+            None => None,
+        };
+
+        let min_severity = match query.get("min_level") {
+            Some(level) => Some(level_severity(level).ok_or_else(|| {
+                bad_request(format!("unknown min_level {}, expected one of {}", level, crate::ACCEPTED_LEVELS.join(", ")))
+            })?),
+            None => None,
+        };
+
+        let since = parse_time_param(query, "since")?;
+        let until = parse_time_param(query, "until")?;
+        if let (Some(since), Some(until)) = (since, until)
+            && until < since
+        {
+            return Err(bad_request("until must not be earlier than since"));
+        }
+
+        let mut metadata: Vec<(String, String)> = query
+            .iter()
+            .filter_map(|(k, v)| k.strip_prefix("meta.").map(|key| (key.to_string(), v.clone())))
+            .collect();
+        metadata.sort();
+
+        Ok(FilterParams {
+            service: query.get("service").cloned(),
+            level: query.get("level").cloned(),
+            min_severity,
+            trace_id: query.get("trace_id").cloned(),
+            q,
+            q_regex,
+            since,
+            until,
+            metadata,
+            namespace: Some(namespace.name.clone()),
+        })
+    }
+
+    // All conditions are ANDed; entries with unparseable timestamps never match a time range
+    pub(crate) fn matches(&self, log: &LogEntry) -> bool {
+        if self.namespace.as_ref().is_some_and(|ns| namespace_of(log) != ns) {
+            return false;
+        }
+        if self.service.as_ref().is_some_and(|s| log.service != *s) {
+            return false;
+        }
+        if self.level.as_ref().is_some_and(|l| log.level != *l) {
+            return false;
+        }
+        if self.min_severity.is_some_and(|min| level_severity(&log.level).is_none_or(|s| s < min)) {
+            return false;
+        }
+        if self.trace_id.is_some() && log.trace_id != self.trace_id {
+            return false;
+        }
+        if self.q_regex.as_ref().is_some_and(|re| !re.is_match(&log.message)) {
+            return false;
+        }
+        // TODO: swap in an inverted-index lookup here once plain substring matching is not enough
+        if self.q.as_ref().is_some_and(|q| !log.message.to_lowercase().contains(q)) {
+            return false;
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Ok(ts) = DateTime::parse_from_rfc3339(&log.timestamp) else {
+                return false;
+            };
+            if self.since.is_some_and(|s| ts < s) || self.until.is_some_and(|u| ts > u) {
+                return false;
+            }
+        }
+        for (key, expected) in &self.metadata {
+            let actual = log.metadata.as_ref().and_then(|m| m.get(key));
+            if actual.is_none_or(|v| meta_value_string(v) != *expected) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// DEBUG(0) < INFO(1) < WARN(2) < ERROR(3) < CRITICAL(4), case-insensitively
+pub(crate) fn level_severity(level: &str) -> Option<u8> {
+    crate::ACCEPTED_LEVELS.iter().position(|l| l.eq_ignore_ascii_case(level)).map(|i| i as u8)
+}
+
+// The canonical levels at least as severe as min_severity, for backends that filter in SQL
+pub(crate) fn levels_from(min_severity: u8) -> &'static [&'static str] {
+    &crate::ACCEPTED_LEVELS[usize::from(min_severity).min(crate::ACCEPTED_LEVELS.len())..]
+}
+
+// Strings compare by their contents, everything else by its JSON text
+pub(crate) fn meta_value_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn bad_request(error: impl Into<String>) -> HttpResponse {
+    HttpResponse::BadRequest().json(serde_json::json!({ "error": error.into() }))
+}
+
+// Parse an optional RFC 3339 query parameter, producing a 400 response if malformed
+pub(crate) fn parse_time_param(
+    query: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<DateTime<FixedOffset>>, HttpResponse> {
+    match query.get(name) {
+        None => Ok(None),
+        Some(raw) => DateTime::parse_from_rfc3339(raw)
+            .map(Some)
+            .map_err(|e| bad_request(format!("invalid {}: {}", name, e))),
+    }
+}
+
+// Compile a user-supplied regex with a bounded size and compilation time
+async fn compile_query_regex(pattern: &str) -> Result<Regex, String> {
+    if pattern.len() > MAX_QUERY_LEN {
+        return Err(format!("q_regex must be at most {} bytes", MAX_QUERY_LEN));
+    }
+
+    let pattern = pattern.to_string();
+    let compile = tokio::task::spawn_blocking(move || {
+        RegexBuilder::new(&pattern)
+            .size_limit(REGEX_SIZE_LIMIT)
+            .dfa_size_limit(REGEX_SIZE_LIMIT)
+            .build()
+    });
+
+    match tokio::time::timeout(REGEX_COMPILE_TIMEOUT, compile).await {
+        Ok(Ok(Ok(re))) => Ok(re),
+        Ok(Ok(Err(e))) => Err(format!("invalid q_regex: {}", e)),
+        Ok(Err(_)) => Err("q_regex compilation failed".to_string()),
+        Err(_) => Err("q_regex compilation timed out".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespace::DEFAULT_NAMESPACE;
+
+    fn entry(service: &str, level: &str, message: &str, timestamp: &str) -> LogEntry {
+        LogEntry {
+            id: 0,
+            timestamp: timestamp.to_string(),
+            service: service.to_string(),
+            level: level.to_string(),
+            message: message.to_string(),
+            metadata: None,
+            trace_id: None,
+            span_id: None,
+            count: 1,
+            namespace: None,
+        }
+    }
+
+    fn sample() -> Vec<LogEntry> {
+        let mut logs = vec![
+            entry("auth", "INFO", "User logged in", "2026-01-01T10:00:00Z"),
+            entry("auth", "ERROR", "Login failed for user", "2026-01-01T11:00:00Z"),
+            entry("orders", "DEBUG", "Cart loaded", "2026-01-01T12:00:00Z"),
+            entry("orders", "CRITICAL", "Payment gateway down", "2026-01-01T13:00:00Z"),
+            entry("orders", "WARN", "Slow payment response", "not a timestamp"),
+        ];
+        logs[1].trace_id = Some("t-1".to_string());
+        logs[3].metadata = Some(HashMap::from([
+            ("region".to_string(), serde_json::json!("eu")),
+            ("retries".to_string(), serde_json::json!(3)),
+        ]));
+        logs[4].namespace = Some("team-b".to_string());
+        logs
+    }
+
+    fn matching(params: &FilterParams) -> Vec<String> {
+        sample().iter().filter(|log| params.matches(log)).map(|log| log.message.clone()).collect()
+    }
+
+    fn time(raw: &str) -> Option<DateTime<FixedOffset>> {
+        Some(DateTime::parse_from_rfc3339(raw).unwrap())
+    }
+
+    async fn from_query(pairs: &[(&str, &str)]) -> Result<FilterParams, HttpResponse> {
+        let query = pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        FilterParams::from_query(&query, &Namespace { name: DEFAULT_NAMESPACE.to_string(), assigned: false }).await
+    }
+
+    #[test]
+    fn no_filters_match_everything() {
+        assert_eq!(matching(&FilterParams::default()).len(), 5);
+    }
+
+    #[test]
+    fn each_filter_on_its_own() {
+        let by_service = FilterParams { service: Some("auth".to_string()), ..Default::default() };
+        assert_eq!(matching(&by_service), ["User logged in", "Login failed for user"]);
+
+        let by_level = FilterParams { level: Some("DEBUG".to_string()), ..Default::default() };
+        assert_eq!(matching(&by_level), ["Cart loaded"]);
+
+        let by_severity = FilterParams { min_severity: level_severity("error"), ..Default::default() };
+        assert_eq!(matching(&by_severity), ["Login failed for user", "Payment gateway down"]);
+
+        let by_trace = FilterParams { trace_id: Some("t-1".to_string()), ..Default::default() };
+        assert_eq!(matching(&by_trace), ["Login failed for user"]);
+
+        let by_text = FilterParams { q: Some("payment".to_string()), ..Default::default() };
+        assert_eq!(matching(&by_text), ["Payment gateway down", "Slow payment response"]);
+
+        let by_regex = FilterParams { q_regex: Some(Regex::new("^(User|Cart) ").unwrap()), ..Default::default() };
+        assert_eq!(matching(&by_regex), ["User logged in", "Cart loaded"]);
+
+        let by_meta = FilterParams { metadata: vec![("retries".to_string(), "3".to_string())], ..Default::default() };
+        assert_eq!(matching(&by_meta), ["Payment gateway down"]);
+
+        let by_namespace = FilterParams { namespace: Some("team-b".to_string()), ..Default::default() };
+        assert_eq!(matching(&by_namespace), ["Slow payment response"]);
+    }
+
+    #[test]
+    fn time_range_is_inclusive_and_skips_unparseable_timestamps() {
+        let since = FilterParams { since: time("2026-01-01T11:00:00Z"), ..Default::default() };
+        assert_eq!(matching(&since), ["Login failed for user", "Cart loaded", "Payment gateway down"]);
+
+        let until = FilterParams { until: time("2026-01-01T11:00:00Z"), ..Default::default() };
+        assert_eq!(matching(&until), ["User logged in", "Login failed for user"]);
+
+        let window = FilterParams {
+            since: time("2026-01-01T12:00:00+01:00"),
+            until: time("2026-01-01T12:00:00Z"),
+            ..Default::default()
+        };
+        assert_eq!(matching(&window), ["Login failed for user", "Cart loaded"]);
+    }
+
+    #[test]
+    fn filters_combine_with_and() {
+        let params = FilterParams {
+            service: Some("orders".to_string()),
+            q: Some("payment".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(matching(&params), ["Payment gateway down", "Slow payment response"]);
+
+        let params = FilterParams { min_severity: level_severity("warn"), ..params };
+        assert_eq!(matching(&params), ["Payment gateway down", "Slow payment response"]);
+
+        let params = FilterParams { namespace: Some(DEFAULT_NAMESPACE.to_string()), ..params };
+        assert_eq!(matching(&params), ["Payment gateway down"]);
+
+        let params = FilterParams { until: time("2026-01-01T12:00:00Z"), ..params };
+        assert!(matching(&params).is_empty());
+
+        let params = FilterParams {
+            service: Some("orders".to_string()),
+            metadata: vec![("region".to_string(), "eu".to_string()), ("retries".to_string(), "4".to_string())],
+            ..Default::default()
+        };
+        assert!(matching(&params).is_empty());
+    }
+
+    #[tokio::test]
+    async fn from_query_parses_and_scopes_to_the_namespace() {
+        let params = from_query(&[("service", "auth"), ("q", "LOGIN"), ("min_level", "warn"), ("meta.region", "eu")])
+            .await
+            .unwrap();
+        assert_eq!(params.q.as_deref(), Some("login"));
+        assert_eq!(params.min_severity, Some(2));
+        assert_eq!(params.metadata, [("region".to_string(), "eu".to_string())]);
+        assert_eq!(params.namespace.as_deref(), Some(DEFAULT_NAMESPACE));
+    }
+
+    #[tokio::test]
+    async fn from_query_rejects_malformed_parameters() {
+        let long = "x".repeat(MAX_QUERY_LEN + 1);
+        for pairs in [
+            vec![("q", "a"), ("q_regex", "a")],
+            vec![("q_regex", "(")],
+            vec![("q", long.as_str())],
+            vec![("min_level", "verbose")],
+            vec![("since", "yesterday")],
+            vec![("since", "2026-01-02T00:00:00Z"), ("until", "2026-01-01T00:00:00Z")],
+        ] {
+            let resp = from_query(&pairs).await.unwrap_err();
+            assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST, "{:?}", pairs);
+        }
+    }
+}
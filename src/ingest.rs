@@ -0,0 +1,136 @@
+use actix_web::web;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+
+use crate::bus::Sinks;
+use crate::dedup::Deduplicator;
+use crate::level_filters::LevelFilters;
+use crate::pii::PiiScrubber;
+use crate::storage::SharedStorage;
+use crate::timestamp::ClockSkewCheck;
+use crate::{ingest_many, prepare_entry, Admission, LogEntry, LogSequence, MessageLimit, ServiceLimit};
+
+// Longest line accepted on a TCP connection before it is dropped
+const MAX_LINE_BYTES: u64 = 1 << 20;
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct IngestConfig {
+    // Newline-delimited JSON over plain TCP, disabled when unset
+    #[serde(default)]
+    pub(crate) tcp_port: Option<u16>,
+    #[serde(default = "default_tcp_max_connections")]
+    pub(crate) tcp_max_connections: usize,
+    // RFC 3164 syslog over UDP, disabled when unset
+    #[serde(default)]
+    pub(crate) udp_syslog_port: Option<u16>,
+}
+
+fn default_tcp_max_connections() -> usize {
+    50
+}
+
+// Everything needed to store entries from outside an HTTP handler exactly as POST /logs does
+#[derive(Clone)]
+pub(crate) struct IngestContext {
+    pub(crate) storage: SharedStorage,
+    pub(crate) seq: LogSequence,
+    pub(crate) pii: web::Data<PiiScrubber>,
+    pub(crate) dedup: web::Data<Deduplicator>,
+    pub(crate) sinks: Sinks,
+    pub(crate) message_limit: web::Data<MessageLimit>,
+    pub(crate) service_limit: web::Data<ServiceLimit>,
+    pub(crate) level_filters: web::Data<LevelFilters>,
+    pub(crate) skew: web::Data<ClockSkewCheck>,
+}
+
+impl IngestContext {
+    // Validate, scrub and store one entry with POST /logs' checks in its order, returning its id,
+    // or None when [level_filters] dropped it. There is no API key to assign a namespace from, so
+    // entries go to "default" whatever they name, like POST /logs without a namespaced key
+    pub(crate) async fn ingest(&self, mut entry: LogEntry) -> Result<Option<u64>, String> {
+        entry.namespace = None;
+        if self.message_limit.exceeds(&entry) {
+            return Err(format!("message too large, max_bytes {}", self.message_limit.max_bytes));
+        }
+        prepare_entry(&mut entry)?;
+        if self.level_filters.drops(&entry) {
+            return Ok(None);
+        }
+        let admission = self.service_limit.admit(&entry.service);
+        if admission == Admission::Full {
+            return Err("service limit reached".to_string());
+        }
+        self.skew.check(&mut entry);
+        self.pii.scrub(&mut entry);
+
+        let service = entry.service.clone();
+        match ingest_many(&self.storage, &self.seq, &self.dedup, &self.sinks, vec![entry]).await {
+            Ok(ids) => Ok(Some(ids[0])),
+            Err(e) => {
+                self.service_limit.release(&service, admission);
+                Err(e.to_string())
+            }
+        }
+    }
+}
+
+// Accept connections until the process exits; connections past the limit wait in the backlog
+pub(crate) async fn tcp_ingestion_task(listener: TcpListener, max_connections: usize, ctx: IngestContext) {
+    let permits = Arc::new(Semaphore::new(max_connections.max(1)));
+
+    loop {
+        let Ok(permit) = permits.clone().acquire_owned().await else {
+            return;
+        };
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    handle_connection(stream, peer, &ctx).await;
+                    drop(permit);
+                });
+            }
+            Err(e) => tracing::warn!(error = %e, "tcp ingest accept failed"),
+        }
+    }
+}
+
+// One JSON LogEntry per line; bad lines are reported and skipped
+async fn handle_connection(stream: TcpStream, peer: SocketAddr, ctx: &IngestContext) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut line_no = 0;
+
+    loop {
+        line.clear();
+        match (&mut reader).take(MAX_LINE_BYTES + 1).read_until(b'\n', &mut line).await {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(%peer, error = %e, "tcp ingest read failed");
+                return;
+            }
+        }
+        line_no += 1;
+
+        if line.len() as u64 > MAX_LINE_BYTES {
+            tracing::warn!(%peer, line_no, max_bytes = MAX_LINE_BYTES, "tcp ingest line too long, closing");
+            return;
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let result = match serde_json::from_slice::<LogEntry>(&line) {
+            Ok(entry) => ctx.ingest(entry).await.map(|_| ()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(reason) = result {
+            tracing::warn!(%peer, line_no, reason, "tcp ingest line rejected");
+        }
+    }
+}
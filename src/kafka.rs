@@ -0,0 +1,200 @@
+use serde::Deserialize;
+
+// Parsed even without the kafka feature so the config still loads, but only the consumer reads it
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+pub(crate) struct KafkaConfig {
+    pub(crate) brokers: Vec<String>,
+    pub(crate) topic: String,
+    pub(crate) group_id: String,
+    // "earliest" or "latest", where a group without committed offsets starts
+    #[serde(default = "default_auto_offset_reset")]
+    pub(crate) auto_offset_reset: String,
+}
+
+fn default_auto_offset_reset() -> String {
+    "latest".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[cfg_attr(not(feature = "kafka"), allow(dead_code))]
+pub(crate) struct KafkaProducerConfig {
+    pub(crate) brokers: Vec<String>,
+    pub(crate) topic: String,
+    // "0", "1" or "all"
+    #[serde(default = "default_acks")]
+    pub(crate) acks: String,
+}
+
+fn default_acks() -> String {
+    "1".to_string()
+}
+
+#[cfg(feature = "kafka")]
+pub(crate) use consumer::KafkaConsumerTask;
+
+// Copies every accepted entry to a Kafka topic; a no-op unless configured and built with the kafka feature
+#[derive(Default)]
+pub(crate) struct KafkaForwarder {
+    #[cfg(feature = "kafka")]
+    producer: Option<producer::Producer>,
+}
+
+impl KafkaForwarder {
+    #[cfg(feature = "kafka")]
+    pub(crate) fn new(cfg: &KafkaProducerConfig, errors: prometheus::IntCounter) -> rdkafka::error::KafkaResult<Self> {
+        Ok(Self { producer: Some(producer::Producer::new(cfg, errors)?) })
+    }
+
+    // Fire and forget, keyed by service so one service's entries stay ordered
+    pub(crate) fn forward(&self, service: &str, payload: &str) {
+        #[cfg(feature = "kafka")]
+        if let Some(producer) = &self.producer {
+            producer.send(service, payload);
+        }
+        #[cfg(not(feature = "kafka"))]
+        let _ = (service, payload);
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod producer {
+    use prometheus::IntCounter;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::error::KafkaResult;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use tokio::time::Duration;
+
+    use super::KafkaProducerConfig;
+
+    // How long a message may wait for delivery, including time queued locally
+    const PRODUCE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub(super) struct Producer {
+        producer: FutureProducer,
+        topic: String,
+        errors: IntCounter,
+    }
+
+    impl Producer {
+        pub(super) fn new(cfg: &KafkaProducerConfig, errors: IntCounter) -> KafkaResult<Self> {
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", cfg.brokers.join(","))
+                .set("acks", &cfg.acks)
+                .set("message.timeout.ms", PRODUCE_TIMEOUT.as_millis().to_string())
+                .create()?;
+            Ok(Self { producer, topic: cfg.topic.clone(), errors })
+        }
+
+        pub(super) fn send(&self, key: &str, payload: &str) {
+            let producer = self.producer.clone();
+            let topic = self.topic.clone();
+            let errors = self.errors.clone();
+            let (key, payload) = (key.to_string(), payload.to_string());
+
+            tokio::spawn(async move {
+                let record = FutureRecord::to(&topic).key(&key).payload(&payload);
+                if let Err((e, _)) = producer.send(record, PRODUCE_TIMEOUT).await {
+                    errors.inc();
+                    tracing::error!(topic, error = %e, "kafka produce failed");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod consumer {
+    use prometheus::IntCounter;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+    use rdkafka::error::KafkaResult;
+    use rdkafka::Message;
+    use tokio::time::{sleep, Duration};
+
+    use super::KafkaConfig;
+    use crate::ingest::IngestContext;
+    use crate::LogEntry;
+
+    // Delay after a failure, doubling up to the cap and reset by the next success
+    const BACKOFF_BASE: Duration = Duration::from_millis(500);
+    const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+    // Consume JSON LogEntry messages from one topic, committing each offset once its entry is stored
+    pub(crate) struct KafkaConsumerTask {
+        pub(crate) cfg: KafkaConfig,
+        pub(crate) ctx: IngestContext,
+        pub(crate) consumed: IntCounter,
+        pub(crate) errors: IntCounter,
+    }
+
+    impl KafkaConsumerTask {
+        fn connect(&self) -> KafkaResult<StreamConsumer> {
+            let consumer: StreamConsumer = ClientConfig::new()
+                .set("bootstrap.servers", self.cfg.brokers.join(","))
+                .set("group.id", &self.cfg.group_id)
+                .set("auto.offset.reset", &self.cfg.auto_offset_reset)
+                .set("enable.auto.commit", "false")
+                .create()?;
+            consumer.subscribe(&[&self.cfg.topic])?;
+            Ok(consumer)
+        }
+
+        pub(crate) async fn run(self) {
+            let mut backoff = BACKOFF_BASE;
+
+            let consumer = loop {
+                match self.connect() {
+                    Ok(consumer) => break consumer,
+                    Err(e) => {
+                        self.errors.inc();
+                        tracing::error!(topic = %self.cfg.topic, error = %e, "kafka cannot subscribe");
+                        sleep(backoff).await;
+                        backoff = (backoff * 2).min(BACKOFF_MAX);
+                    }
+                }
+            };
+
+            loop {
+                // librdkafka reconnects to brokers on its own; errors here are transient
+                let msg = match consumer.recv().await {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        self.errors.inc();
+                        tracing::warn!(error = %e, "kafka receive failed");
+                        sleep(backoff).await;
+                        backoff = (backoff * 2).min(BACKOFF_MAX);
+                        continue;
+                    }
+                };
+                backoff = BACKOFF_BASE;
+
+                let stored = match msg.payload().map(serde_json::from_slice::<LogEntry>) {
+                    Some(Ok(entry)) => self.ctx.ingest(entry).await,
+                    Some(Err(e)) => Err(e.to_string()),
+                    None => Err("empty message".to_string()),
+                };
+                match stored {
+                    Ok(_) => {
+                        self.consumed.inc();
+                        if let Err(e) = consumer.commit_message(&msg, CommitMode::Async) {
+                            self.errors.inc();
+                            tracing::warn!(error = %e, "kafka commit failed");
+                        }
+                    }
+                    // Left uncommitted; the next stored entry's commit moves the group past it
+                    Err(reason) => {
+                        self.errors.inc();
+                        tracing::warn!(
+                            topic = msg.topic(),
+                            partition = msg.partition(),
+                            offset = msg.offset(),
+                            reason,
+                            "kafka message rejected"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
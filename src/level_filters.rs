@@ -0,0 +1,39 @@
+use prometheus::IntCounterVec;
+use std::collections::HashMap;
+
+use crate::filters::level_severity;
+use crate::LogEntry;
+
+// [level_filters] maps a service to the least severe level ingest still stores for it
+pub(crate) type LevelFilterConfig = HashMap<String, String>;
+
+pub(crate) struct LevelFilters {
+    min_severity: HashMap<String, u8>,
+    filtered: IntCounterVec,
+}
+
+impl LevelFilters {
+    // Unknown level names fail startup instead of silently letting everything through
+    pub(crate) fn new(cfg: &LevelFilterConfig, filtered: IntCounterVec) -> Result<Self, String> {
+        let min_severity = cfg
+            .iter()
+            .map(|(service, level)| match level_severity(level) {
+                Some(severity) => Ok((service.clone(), severity)),
+                None => Err(format!("level_filters.{}: unknown level {:?}", service, level)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { min_severity, filtered })
+    }
+
+    // Whether a prepared entry falls below its service's minimum and should be dropped
+    pub(crate) fn drops(&self, entry: &LogEntry) -> bool {
+        let Some(&min) = self.min_severity.get(&entry.service) else {
+            return false;
+        };
+        if level_severity(&entry.level).is_none_or(|severity| severity >= min) {
+            return false;
+        }
+        self.filtered.with_label_values(&[entry.service.as_str(), entry.level.as_str()]).inc();
+        true
+    }
+}
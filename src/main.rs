@@ -1,33 +1,207 @@
-use actix_web::{web, App, HttpServer, Responder, HttpResponse};
+// Helpers return a ready-made HttpResponse as their error so handlers can bail out early
+#![allow(clippy::result_large_err)]
+// Handlers take one web::Data extractor per piece of shared state
+#![allow(clippy::too_many_arguments)]
+
+use actix_web::{web, App, HttpRequest, HttpServer, Responder, HttpResponse};
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::header;
 use actix_files::NamedFile;
+use actix_cors::Cors;
+use actix_web::middleware::{Compress, Condition};
+use actix_multipart::Multipart;
 use serde::{Serialize, Deserialize};
-use chrono::Utc;
-use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
+use chrono::{DateTime, DurationRound, FixedOffset, NaiveDate, TimeDelta, Utc};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::{broadcast, broadcast::error::RecvError, RwLock};
+use futures::StreamExt;
+use tokio_stream::wrappers::IntervalStream;
 use bytes::Bytes;
-use std::fs::OpenOptions;
-use std::io::Write;
-use tokio::time::{sleep, Duration};
-use prometheus::{Encoder, TextEncoder, IntCounter, Registry};
+use std::io::{Read, Write as _};
+use tokio::io::AsyncWriteExt;
+use std::path::Path;
+use tokio::time::{sleep, Duration, Instant};
+use prometheus::{CounterVec, Encoder, Gauge, HistogramOpts, HistogramVec, Opts, TextEncoder, IntCounter, IntCounterVec, IntGauge, Registry};
+use clap::Parser;
 use config::Config;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+mod alerts;
+mod backup;
+mod bus;
+mod cors;
+mod dedup;
+mod encryption;
+mod field_mapping;
+mod filters;
+mod ingest;
+mod kafka;
+mod level_filters;
+mod middleware;
+mod namespace;
+mod openapi;
+mod pii;
+mod query_cache;
+mod rate_limit;
+mod rooms;
+mod signing;
+mod similarity;
+mod storage;
+mod syslog;
+mod telemetry;
+mod timestamp;
+mod ws;
+use alerts::AlertConfig;
+use backup::S3BackupConfig;
+use bus::{BroadcastSink, LogBus, MetricsSink, Sinks};
+use cors::CorsConfig;
+use dedup::Deduplicator;
+use encryption::LineCipher;
+use field_mapping::FieldMapping;
+use filters::FilterParams;
+use ingest::{IngestConfig, IngestContext};
+use kafka::{KafkaConfig, KafkaForwarder, KafkaProducerConfig};
+use level_filters::{LevelFilterConfig, LevelFilters};
+use middleware::{
+    secret_eq, ApiKeyMiddleware, CorsPreflightMiddleware, GzipLevelMiddleware, HmacAuthMiddleware, PromMiddleware,
+    RequestId, RequestIdMiddleware, SeenSignatures,
+};
+use namespace::{namespace_of, Namespace, Namespaces};
+use openapi::{
+    Accepted, AggregateGroup, ApiDoc, BatchResult, Count, ErrorRatePoint, ErrorResponse, Fields, Health, ImportResult,
+    LevelRejected, Levels, LimitExceeded, LogDiff, LogNotFound, LogPage, NotReady, Purged, Ready, ServiceGraph,
+    ServiceHealth, Services, SimilarLogs, StatsResponse, TopError, Verified,
+};
+use pii::{PiiConfig, PiiScrubber};
+use query_cache::QueryCache;
+use rate_limit::{RateLimitBuckets, RateLimitConfig, TokenBucket};
+use rooms::{Rooms, GLOBAL_ROOM};
+use storage::{
+    MemoryStorage, PostgresConfig, PostgresStorage, RedisConfig, RedisStorage, SharedStorage, SqliteStorage, Storage,
+    StorageError,
+};
+use telemetry::ObservabilityConfig;
+use timestamp::ClockSkewCheck;
+use tracing::Instrument;
+use tracing_actix_web::TracingLogger;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub(crate) struct LogEntry {
+    // Assigned by the server on ingest; any client-supplied value is ignored
+    #[serde(default)]
+    id: u64,
+    // Numbers are kept as their digits until prepare_entry reads them as epoch time
+    #[serde(deserialize_with = "timestamp::string_or_number")]
     timestamp: String,
     service: String,
     level: String,
     message: String,
+    // Structured context such as user_id or duration_ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<std::collections::HashMap<String, serde_json::Value>>,
+    // Distributed tracing correlation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    span_id: Option<String>,
+    // Occurrences merged into this entry when deduplication is enabled
+    #[serde(default = "default_count")]
+    count: usize,
+    // Tenant the entry belongs to, set from the namespaced API key on ingest whatever the client sent;
+    // None reads as "default"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    namespace: Option<String>,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+// What live subscribers receive: a serialised entry, either new or an update of one already sent
+#[derive(Debug, Clone)]
+pub(crate) struct LogEvent {
+    pub(crate) payload: String,
+    pub(crate) updated: bool,
+}
+
+// Log levels accepted on ingest, in canonical form, least severe first
+const ACCEPTED_LEVELS: [&str; 5] = ["DEBUG", "INFO", "WARN", "ERROR", "CRITICAL"];
+
+// Map a level to its canonical uppercase form, if it is one we accept
+fn normalize_level(level: &str) -> Option<&'static str> {
+    let upper = level.trim().to_uppercase();
+    ACCEPTED_LEVELS.iter().copied().find(|l| *l == upper)
 }
 
-// Shared in-memory storage
-type LogDb = Arc<Mutex<Vec<LogEntry>>>;
-type Broadcaster = Arc<broadcast::Sender<String>>;
+// Flush attempts per persistence cycle, with the delay doubling from PERSIST_RETRY_BASE
+const PERSIST_ATTEMPTS: u32 = 3;
+const PERSIST_RETRY_BASE: Duration = Duration::from_millis(200);
+
+// Shared in-memory storage, oldest entries at the front
+pub(crate) type LogDb = Arc<RwLock<VecDeque<LogEntry>>>;
+type Broadcaster = Arc<broadcast::Sender<LogEvent>>;
+// Monotonic id counter, restarts at 1 on every boot
+type LogSequence = Arc<AtomicU64>;
+// Set once the persistence file has been written successfully
+type PersistReady = Arc<AtomicBool>;
 
 // Load configuration
 #[derive(Debug, Deserialize, Clone)]
 struct ServerConfig {
     host: String,
     port: u16,
+    // Bearer token required by admin endpoints; they are disabled when unset
+    #[serde(default)]
+    admin_token: Option<String>,
+    // Required as X-API-Key on ingest and delete endpoints when set
+    #[serde(default)]
+    api_key: Option<String>,
+    // Further X-API-Key values, each tying its requests to a namespace: entries it ingests are put
+    // there, and reads only see that namespace
+    #[serde(default)]
+    namespaces: std::collections::HashMap<String, String>,
+    // Largest POST /logs body once decompressed; gzip and zstd bodies are decoded as they arrive
+    #[serde(default = "default_max_body_bytes")]
+    max_body_bytes: usize,
+    // Seconds between SSE comment frames on /logs/stream
+    #[serde(default = "default_heartbeat_interval_secs")]
+    heartbeat_interval_secs: u64,
+    // Messages a subscriber may fall behind before it starts missing entries
+    #[serde(default = "default_broadcast_capacity")]
+    broadcast_capacity: usize,
+    // Per-service /logs/stream rooms are dropped after this long without subscribers
+    #[serde(default = "default_room_ttl_secs")]
+    room_ttl_secs: u64,
+    // gzip level 0-9 for complete response bodies; unset leaves it to actix, which uses level 1
+    #[serde(default)]
+    compress_level: Option<u32>,
+    // Ingest and delete endpoints take an HMAC signature from one of the keys above instead of the
+    // key itself in X-API-Key, see HmacAuthMiddleware
+    #[serde(default)]
+    hmac_auth: bool,
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_broadcast_capacity() -> usize {
+    100
+}
+
+fn default_room_ttl_secs() -> u64 {
+    300
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    15
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -35,12 +209,198 @@ struct LoggingConfig {
     file_path: String,
     max_memory_logs: usize,
     persist_interval_secs: u64,
+    // Truncate the persisted file at boot instead of loading it
+    #[serde(default)]
+    overwrite_on_start: bool,
+    #[serde(default = "default_max_batch_size")]
+    max_batch_size: usize,
+    // POST /logs and the TCP, syslog and Kafka inputs reject longer messages, batch and import truncate them
+    #[serde(default = "default_max_message_bytes")]
+    max_message_bytes: usize,
+    // Rotate the persisted file once it grows past this size
+    #[serde(default)]
+    max_file_size_mb: Option<u64>,
+    // Rotate the persisted file when the UTC date changes
+    #[serde(default)]
+    rotate_daily: bool,
+    // Upper bound for n on GET /logs/tail
+    #[serde(default = "default_max_tail")]
+    max_tail: usize,
+    // How long a paginated GET /logs result is served from cache, 0 disables it
+    #[serde(default = "default_query_cache_ttl_ms")]
+    query_cache_ttl_ms: u64,
+    #[serde(default = "default_max_rotated_files")]
+    max_rotated_files: usize,
+    // Gzip persisted output, written to <file_path>.gz
+    #[serde(default)]
+    compress_persisted: bool,
+    // "memory_file" keeps logs in memory and persists them to file_path, "sqlite" stores them in sqlite_url,
+    // "redis" in the [redis] server, "postgres" in the [postgres] database
+    #[serde(default = "default_storage_backend")]
+    storage_backend: String,
+    #[serde(default = "default_sqlite_url")]
+    sqlite_url: String,
+    // Entries older than this are evicted every evict_interval_secs
+    #[serde(default)]
+    max_age_secs: Option<u64>,
+    #[serde(default = "default_evict_interval_secs")]
+    evict_interval_secs: u64,
+    // Upper bound on a POST /logs/import upload
+    #[serde(default = "default_import_max_mb")]
+    import_max_mb: u64,
+    // Entries POST /logs may queue for the dispatcher before it answers 503
+    #[serde(default = "default_bus_capacity")]
+    bus_capacity: usize,
+    // Merge entries with the same service, level and message into one with a count. With memory_file
+    // the count grows in memory only; the file keeps what it was when the entry was first flushed
+    #[serde(default)]
+    enable_deduplication: bool,
+    #[serde(default = "default_max_dedup_entries")]
+    max_dedup_entries: usize,
+    // Entries from POST /logs and the other inputs further than this from server time are flagged with
+    // _clock_skew_warning
+    #[serde(default = "default_max_clock_skew_secs")]
+    max_clock_skew_secs: u64,
+    // GET /logs/service_health marks services below this score as degraded
+    #[serde(default = "default_health_warn_threshold")]
+    health_warn_threshold: f64,
+    // GET /logs/similar/{id} lists entries whose message scores above this, from 0 to 1
+    #[serde(default = "default_similarity_threshold")]
+    similarity_threshold: f64,
+    // Persisted lines carry an HMAC-SHA256 of the entry under this key, checked by POST /logs/verify
+    #[serde(default)]
+    signing_key: Option<String>,
+    // 64 hex characters; persisted lines are then AES-256-GCM encrypted and base64 encoded one by one
+    #[serde(default)]
+    encryption_key_hex: Option<String>,
+    // Distinct service names ingest will accept; entries from further services are rejected
+    #[serde(default)]
+    max_services: Option<usize>,
+}
+
+fn default_max_batch_size() -> usize {
+    1000
+}
+
+fn default_max_message_bytes() -> usize {
+    8192
+}
+
+fn default_max_tail() -> usize {
+    1000
+}
+
+fn default_query_cache_ttl_ms() -> u64 {
+    500
+}
+
+fn default_max_rotated_files() -> usize {
+    5
+}
+
+fn default_evict_interval_secs() -> u64 {
+    60
+}
+
+fn default_max_clock_skew_secs() -> u64 {
+    300
+}
+
+fn default_similarity_threshold() -> f64 {
+    0.7
+}
+
+fn default_health_warn_threshold() -> f64 {
+    0.9
+}
+
+fn default_bus_capacity() -> usize {
+    10000
+}
+
+fn default_max_dedup_entries() -> usize {
+    10000
+}
+
+fn default_import_max_mb() -> u64 {
+    10
+}
+
+fn default_storage_backend() -> String {
+    "memory_file".to_string()
+}
+
+fn default_sqlite_url() -> String {
+    "sqlite://logs.db".to_string()
+}
+
+// Serve HTTPS instead of plain HTTP when present
+#[derive(Debug, Deserialize, Clone)]
+struct TlsConfig {
+    cert_pem_path: String,
+    key_pem_path: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 struct AppConfig {
     server: ServerConfig,
     logging: LoggingConfig,
+    #[serde(default)]
+    tls: Option<TlsConfig>,
+    // Cross-origin access for dashboards served from elsewhere, none when unset
+    #[serde(default)]
+    cors: Option<CorsConfig>,
+    // Per-client token bucket applied to POST /logs
+    #[serde(default)]
+    rate_limit: Option<RateLimitConfig>,
+    // OTLP trace export of every HTTP request
+    #[serde(default)]
+    observability: Option<ObservabilityConfig>,
+    // Periodic off-site copy of the persisted log file
+    #[serde(default)]
+    s3_backup: Option<S3BackupConfig>,
+    // Regexes redacted from messages and metadata before anything is stored
+    #[serde(default)]
+    pii_scrubbing: Option<PiiConfig>,
+    // Names POST /logs reads timestamp, service, level and message from
+    #[serde(default)]
+    field_mapping: FieldMapping,
+    // Per service, entries less severe than this level are dropped on ingest
+    #[serde(default)]
+    level_filters: LevelFilterConfig,
+    // Webhooks fired when a level's per-minute volume passes a threshold
+    #[serde(default)]
+    alerts: Vec<AlertConfig>,
+    // Ingestion paths other than HTTP
+    #[serde(default)]
+    ingest: Option<IngestConfig>,
+    // Consumed as an extra ingestion path when built with the kafka feature
+    #[serde(default)]
+    kafka: Option<KafkaConfig>,
+    // Every accepted entry is also produced here when built with the kafka feature
+    #[serde(default)]
+    kafka_producer: Option<KafkaProducerConfig>,
+    // Required by storage_backend = "redis"
+    #[serde(default)]
+    redis: Option<RedisConfig>,
+    // Required by storage_backend = "postgres"
+    #[serde(default)]
+    postgres: Option<PostgresConfig>,
+    #[serde(skip, default = "Instant::now")]
+    started_at: Instant,
+}
+
+/// log aggregator server, configured by config.toml in the working directory
+#[derive(Parser, Debug)]
+#[command(name = "Log_aggregator_Rust")]
+struct Args {
+    /// write entries not yet persisted to the log file on SIGTERM or Ctrl+C; pass false to skip
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    flush_on_exit: bool,
+
+    /// how long to keep retrying a sqlite, redis or postgres backend that is not reachable yet
+    #[arg(long, default_value_t = 10, value_name = "SECS")]
+    startup_timeout_secs: u64,
 }
 
 fn load_config() -> AppConfig {
@@ -52,170 +412,3261 @@ fn load_config() -> AppConfig {
     settings.try_deserialize().unwrap()
 }
 
+// Keep memory bounded by dropping the oldest entries
+pub(crate) fn evict_overflow(logs: &mut VecDeque<LogEntry>, max_len: usize) {
+    while logs.len() > max_len {
+        logs.pop_front();
+    }
+}
+
+// Fill in defaults and validate an incoming entry before it is stored
+fn prepare_entry(entry: &mut LogEntry) -> Result<(), &'static str> {
+    entry.timestamp = timestamp::normalize_timestamp(&entry.timestamp, &entry.service);
+
+    match normalize_level(&entry.level) {
+        Some(level) => entry.level = level.to_string(),
+        None => return Err("invalid level"),
+    }
+    entry.count = entry.count.max(1);
+
+    Ok(())
+}
+
+// Appended to messages cut down to max_message_bytes
+const TRUNCATED_SUFFIX: &str = "[TRUNCATED]";
+
+// Upper bound on an entry's message, rejected on POST /logs and the non-HTTP inputs and truncated in batches
+struct MessageLimit {
+    max_bytes: usize,
+    oversized: IntCounter,
+}
+
+impl MessageLimit {
+    // Counts the entry as oversized when it is
+    fn exceeds(&self, entry: &LogEntry) -> bool {
+        if entry.message.len() <= self.max_bytes {
+            return false;
+        }
+        self.oversized.inc();
+        true
+    }
+
+    fn check(&self, entry: &LogEntry) -> Result<(), HttpResponse> {
+        if !self.exceeds(entry) {
+            return Ok(());
+        }
+        Err(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "message too large",
+            "max_bytes": self.max_bytes
+        })))
+    }
+
+    // Cut at a character boundary so the message, suffix included, fits the limit
+    fn truncate(&self, entry: &mut LogEntry) {
+        if entry.message.len() <= self.max_bytes {
+            return;
+        }
+        self.oversized.inc();
+        let mut end = self.max_bytes.saturating_sub(TRUNCATED_SUFFIX.len());
+        while !entry.message.is_char_boundary(end) {
+            end -= 1;
+        }
+        entry.message.truncate(end);
+        entry.message.push_str(TRUNCATED_SUFFIX);
+    }
+}
+
+// What ServiceLimit made of an entry's service
+#[derive(Clone, Copy, PartialEq)]
+enum Admission {
+    Known,
+    // Took a free slot, to be released if the entry ends up not stored
+    New,
+    Full,
+}
+
+// Caps the distinct service names ingest accepts, so by_service maps cannot grow without bound
+struct ServiceLimit {
+    max: Option<usize>,
+    seen: std::sync::Mutex<HashSet<String>>,
+    rejected: IntCounter,
+}
+
+// Every service with an entry in storage
+async fn stored_services(storage: &SharedStorage) -> Result<HashSet<String>, StorageError> {
+    let mut seen = HashSet::new();
+    storage
+        .scan(&FilterParams::default(), &mut |log| {
+            if !seen.contains(&log.service) {
+                seen.insert(log.service.clone());
+            }
+        })
+        .await?;
+    Ok(seen)
+}
+
+impl ServiceLimit {
+    // Seeded with the services already stored, so a restart does not reopen the limit
+    async fn new(max: Option<usize>, storage: &SharedStorage, rejected: IntCounter) -> Result<Self, StorageError> {
+        let seen = match max {
+            Some(_) => stored_services(storage).await?,
+            None => HashSet::new(),
+        };
+        Ok(Self { max, seen: std::sync::Mutex::new(seen), rejected })
+    }
+
+    // Known services always pass; an unknown one takes a slot while there is room
+    fn admit(&self, service: &str) -> Admission {
+        let Some(max) = self.max else {
+            return Admission::Known;
+        };
+        let mut seen = self.seen.lock().unwrap();
+        if seen.contains(service) {
+            return Admission::Known;
+        }
+        if seen.len() >= max {
+            self.rejected.inc();
+            return Admission::Full;
+        }
+        seen.insert(service.to_string());
+        Admission::New
+    }
+
+    fn check(&self, entry: &LogEntry) -> Result<Admission, HttpResponse> {
+        match self.admit(&entry.service) {
+            Admission::Full => {
+                Err(HttpResponse::BadRequest().json(serde_json::json!({ "error": "service limit reached" })))
+            }
+            admission => Ok(admission),
+        }
+    }
+
+    // Give back the slot of an entry that was admitted but then not stored
+    fn release(&self, service: &str, admission: Admission) {
+        if admission == Admission::New {
+            self.seen.lock().unwrap().remove(service);
+        }
+    }
+
+    // After DELETE /logs, with or without ?service=
+    fn forget(&self, service: Option<&str>) {
+        let mut seen = self.seen.lock().unwrap();
+        match service {
+            Some(service) => {
+                seen.remove(service);
+            }
+            None => seen.clear(),
+        }
+    }
+
+    // After a purge, which may have removed a service's last entry
+    async fn reseed(&self, storage: &SharedStorage) -> Result<(), StorageError> {
+        if self.max.is_none() {
+            return Ok(());
+        }
+        let stored = stored_services(storage).await?;
+        *self.seen.lock().unwrap() = stored;
+        Ok(())
+    }
+}
+
+// Build a rustls server config from the PEM files named in TlsConfig
+fn load_tls_config(tls: &TlsConfig) -> std::io::Result<rustls::ServerConfig> {
+    fn tls_error(what: &str, path: &str, e: std::io::Error) -> std::io::Error {
+        std::io::Error::new(e.kind(), format!("{} {}: {}", what, path, e))
+    }
+
+    let cert_file = std::fs::File::open(&tls.cert_pem_path)
+        .map_err(|e| tls_error("cannot open TLS certificate", &tls.cert_pem_path, e))?;
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(cert_file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| tls_error("malformed TLS certificate", &tls.cert_pem_path, e))?;
+    if certs.is_empty() {
+        return Err(std::io::Error::other(format!("no certificates found in {}", tls.cert_pem_path)));
+    }
+
+    let key_file = std::fs::File::open(&tls.key_pem_path)
+        .map_err(|e| tls_error("cannot open TLS private key", &tls.key_pem_path, e))?;
+    let key = rustls_pemfile::private_key(&mut std::io::BufReader::new(key_file))
+        .map_err(|e| tls_error("malformed TLS private key", &tls.key_pem_path, e))?
+        .ok_or_else(|| std::io::Error::other(format!("no private key found in {}", tls.key_pem_path)))?;
+
+    rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| std::io::Error::other(format!("invalid TLS configuration: {}", e)))
+}
+
+// Charge one token to the caller's bucket, producing a 429 response when it is empty
+fn check_rate_limit(req: &HttpRequest) -> Result<(), HttpResponse> {
+    let Some(rate_cfg) = req.app_data::<web::Data<AppConfig>>().and_then(|c| c.rate_limit.clone()) else {
+        return Ok(());
+    };
+    let (Some(buckets), Some(peer)) = (req.app_data::<web::Data<RateLimitBuckets>>(), req.peer_addr()) else {
+        return Ok(());
+    };
+
+    let result = buckets
+        .lock()
+        .unwrap()
+        .entry(peer.ip())
+        .or_insert_with(|| TokenBucket::new(&rate_cfg))
+        .try_take();
+
+    result.map_err(|wait| {
+        let retry_after_ms = wait.as_millis() as u64;
+        HttpResponse::TooManyRequests()
+            .append_header(("Retry-After", wait.as_secs_f64().ceil().max(1.0).to_string()))
+            .json(serde_json::json!({
+                "error": "rate limit exceeded",
+                "retry_after_ms": retry_after_ms
+            }))
+    })
+}
+
+// Bodies are decompressed per their Content-Encoding while being read, and the limit counts the
+// decompressed bytes, so an oversized body is refused without inflating the rest of it
+fn post_log_json_config(max_body_bytes: usize) -> web::JsonConfig {
+    web::JsonConfig::default().limit(max_body_bytes).error_handler(move |err, _req| match err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            let resp = HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                "error": "body too large",
+                "max_body_bytes": max_body_bytes
+            }));
+            InternalError::from_response(err, resp).into()
+        }
+        err => err.into(),
+    })
+}
+
 // POST /logs
+#[utoipa::path(
+    post,
+    path = "/v1/logs",
+    request_body(content = LogEntry, description = "Field names follow [field_mapping]"),
+    params(
+        ("X-Request-ID" = Option<String>, Header, description = "Reused as _request_id when a valid UUID"),
+    ),
+    responses(
+        (status = 200, description = "Dropped by [level_filters], not stored", body = Accepted),
+        (status = 202, description = "Queued for storage", body = Accepted),
+        (status = 400, description = "Unmappable entry, message too large or service limit reached", body = ErrorResponse),
+        (status = 401, description = "Missing or wrong X-API-Key", body = ErrorResponse),
+        (status = 413, description = "Body too large", body = LimitExceeded),
+        (status = 422, description = "Unknown level", body = LevelRejected),
+        (status = 429, description = "Rate limited", body = ErrorResponse),
+        (status = 503, description = "Ingest queue full", body = ErrorResponse),
+    ),
+    security((), ("api_key" = [])),
+)]
+// Storage and broadcast happen in the dispatcher, whose own spans cover them; this one ends once the
+// entry is queued
+#[tracing::instrument(skip_all, fields(namespace = %namespace.name))]
 async fn post_log(
-    db: web::Data<LogDb>,
-    bcast: web::Data<Broadcaster>,
-    log: web::Json<LogEntry>,
-    total_logs: web::Data<IntCounter>,
+    req: HttpRequest,
+    bus: web::Data<LogBus>,
+    log: web::Json<serde_json::Value>,
+    mapping: web::Data<FieldMapping>,
+    skew: web::Data<ClockSkewCheck>,
+    message_limit: web::Data<MessageLimit>,
+    service_limit: web::Data<ServiceLimit>,
+    level_filters: web::Data<LevelFilters>,
+    pii: web::Data<PiiScrubber>,
+    request_id: web::ReqData<RequestId>,
+    namespace: Namespace,
 ) -> impl Responder {
-    let mut entry = log.into_inner();
+    if let Err(resp) = check_rate_limit(&req) {
+        return resp;
+    }
 
-    if entry.timestamp.trim().is_empty() {
-        entry.timestamp = Utc::now().to_rfc3339();
+    let validation = tracing::trace_span!("validation").entered();
+    let mut entry = match mapping.apply(log.into_inner()) {
+        Ok(entry) => entry,
+        Err(reason) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": reason })),
+    };
+    if let Err(resp) = message_limit.check(&entry) {
+        return resp;
     }
 
-    {
-        let mut db_lock = db.lock().unwrap();
-        db_lock.push(entry.clone());
+    // Lets the request span be found from the trace the log line belongs to
+    if let Some(trace_id) = &entry.trace_id {
+        tracing::Span::current().set_attribute("log.trace_id", trace_id.clone());
+    }
+
+    if let Err(reason) = prepare_entry(&mut entry) {
+        return HttpResponse::UnprocessableEntity().json(serde_json::json!({
+            "error": reason,
+            "accepted": ACCEPTED_LEVELS
+        }));
+    }
+    // Not an error to the client, the service's operators asked for these to be discarded
+    if level_filters.drops(&entry) {
+        return HttpResponse::Ok().json(serde_json::json!({ "message": "Log filtered" }));
+    }
+    let admission = match service_limit.check(&entry) {
+        Ok(admission) => admission,
+        Err(resp) => return resp,
+    };
+    skew.check(&mut entry);
+    pii.scrub(&mut entry);
+    namespace.assign(&mut entry);
+    // Ties what subscribers see back to the request, and to its X-Request-ID
+    entry
+        .metadata
+        .get_or_insert_default()
+        .insert("_request_id".to_string(), serde_json::Value::String(request_id.0.to_string()));
+    drop(validation);
+
+    // Stored and broadcast by the dispatcher; a full queue is pushed back to the client
+    let _enqueue = tracing::trace_span!("enqueue").entered();
+    match bus.try_send(entry) {
+        Ok(()) => HttpResponse::Accepted().json(serde_json::json!({ "message": "Log accepted" })),
+        Err(e) => {
+            service_limit.release(&e.into_inner().service, admission);
+            HttpResponse::ServiceUnavailable().json(serde_json::json!({ "error": "ingest queue full" }))
+        }
+    }
+}
+
+// POST /logs/batch
+#[utoipa::path(
+    post,
+    path = "/v1/logs/batch",
+    request_body = Vec<LogEntry>,
+    responses(
+        (status = 200, description = "Accepted count, with the index of each rejected entry", body = BatchResult),
+        (status = 401, description = "Missing or wrong X-API-Key", body = ErrorResponse),
+        (status = 413, description = "Too many entries", body = LimitExceeded),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+    security((), ("api_key" = [])),
+)]
+async fn post_logs_batch(
+    storage: web::Data<SharedStorage>,
+    logs: web::Json<Vec<LogEntry>>,
+    cfg: web::Data<LoggingConfig>,
+    seq: web::Data<LogSequence>,
+    pii: web::Data<PiiScrubber>,
+    dedup: web::Data<Deduplicator>,
+    sinks: web::Data<Sinks>,
+    message_limit: web::Data<MessageLimit>,
+    service_limit: web::Data<ServiceLimit>,
+    level_filters: web::Data<LevelFilters>,
+    namespace: Namespace,
+) -> impl Responder {
+    let logs = logs.into_inner();
+
+    if logs.len() > cfg.max_batch_size {
+        return HttpResponse::PayloadTooLarge().json(serde_json::json!({
+            "error": "batch too large",
+            "max_batch_size": cfg.max_batch_size
+        }));
+    }
+
+    let mut accepted = Vec::with_capacity(logs.len());
+    let mut rejected = Vec::new();
+    let mut admitted = Vec::new();
+
+    for (index, mut entry) in logs.into_iter().enumerate() {
+        // A batch keeps its oversized entries, just shortened
+        message_limit.truncate(&mut entry);
+        if let Err(reason) = prepare_entry(&mut entry) {
+            rejected.push(serde_json::json!({ "index": index, "reason": reason }));
+            continue;
+        }
+        // Neither accepted nor rejected
+        if level_filters.drops(&entry) {
+            continue;
+        }
+        match service_limit.admit(&entry.service) {
+            Admission::Full => {
+                rejected.push(serde_json::json!({ "index": index, "reason": "service limit reached" }));
+                continue;
+            }
+            admission => admitted.push((entry.service.clone(), admission)),
+        }
+        pii.scrub(&mut entry);
+        namespace.assign(&mut entry);
+        accepted.push(entry);
+    }
+
+    let accepted = match ingest_many(&storage, &seq, &dedup, &sinks, accepted).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            for (service, admission) in &admitted {
+                service_limit.release(service, *admission);
+            }
+            return storage_error(e);
+        }
+    };
+    HttpResponse::Ok().json(serde_json::json!({
+        "accepted": accepted.len(),
+        "rejected": rejected
+    }))
+}
+
+// Number and store prepared entries, merging duplicates when deduplication is on, and hand them
+// to the sinks. Returns the id each entry ended up under, in input order
+async fn ingest_many(
+    storage: &SharedStorage,
+    seq: &LogSequence,
+    dedup: &Deduplicator,
+    sinks: &Sinks,
+    entries: Vec<LogEntry>,
+) -> Result<Vec<u64>, StorageError> {
+    let mut ids = Vec::with_capacity(entries.len());
+    let mut fresh: Vec<LogEntry> = Vec::with_capacity(entries.len());
+    let mut updated: Vec<LogEntry> = Vec::new();
+    // Duplicates within this call, keyed to their position in fresh
+    let mut pending: std::collections::HashMap<dedup::Fingerprint, usize> = std::collections::HashMap::new();
+
+    for mut entry in entries {
+        sinks.received(&entry);
 
-        // Keep memory bounded safely
-        let len = db_lock.len();
-        if len > 50_000 {
-            db_lock.drain(0..len - 50_000);
+        if dedup.enabled() {
+            let fp = dedup::fingerprint(&entry);
+            if let Some(&i) = pending.get(&fp) {
+                fresh[i].count += entry.count;
+                ids.push(fresh[i].id);
+                continue;
+            }
+            if let Some(existing) = dedup.merge(storage, fp, entry.count).await? {
+                ids.push(existing.id);
+                updated.push(existing);
+                continue;
+            }
+            pending.insert(fp, fresh.len());
         }
+
+        entry.id = seq.fetch_add(1, Ordering::Relaxed) + 1;
+        ids.push(entry.id);
+        fresh.push(entry);
+    }
+
+    // Waiting on the store's write lock dominates this under load
+    storage.insert_many(&fresh).instrument(tracing::trace_span!("lock_acquire")).await?;
+    for (fp, i) in pending {
+        dedup.record(fp, fresh[i].id);
     }
 
-    // Broadcast to SSE subscribers
-    if let Ok(payload) = serde_json::to_string(&entry) {
-        let _ = bcast.send(payload);
+    let _broadcast = tracing::trace_span!("broadcast", entries = fresh.len() + updated.len()).entered();
+    for entry in &fresh {
+        sinks.stored(entry, false);
+    }
+    for entry in &updated {
+        sinks.stored(entry, true);
     }
+    tracing::trace!(stored = fresh.len(), merged = updated.len(), "ingested entries");
 
-    total_logs.inc(); // increment Prometheus counter
-    HttpResponse::Ok().body("Log added")
+    Ok(ids)
 }
 
-// GET /logs?service=...&level=...
-async fn get_logs(
-    db: web::Data<LogDb>,
-    query: web::Query<std::collections::HashMap<String, String>>,
+// POST /logs/import, multipart upload with an NDJSON "file" field
+#[utoipa::path(
+    post,
+    path = "/v1/logs/import",
+    request_body(content_type = "multipart/form-data", description = "NDJSON in a field named file"),
+    responses(
+        (status = 200, description = "Accepted and rejected counts, with the line of each error", body = ImportResult),
+        (status = 400, description = "Malformed upload or no file field", body = ErrorResponse),
+        (status = 401, description = "Missing or wrong X-API-Key", body = ErrorResponse),
+        (status = 413, description = "Upload larger than import_max_mb", body = LimitExceeded),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+    security((), ("api_key" = [])),
+)]
+async fn import_logs(
+    storage: web::Data<SharedStorage>,
+    cfg: web::Data<LoggingConfig>,
+    seq: web::Data<LogSequence>,
+    pii: web::Data<PiiScrubber>,
+    dedup: web::Data<Deduplicator>,
+    sinks: web::Data<Sinks>,
+    message_limit: web::Data<MessageLimit>,
+    service_limit: web::Data<ServiceLimit>,
+    level_filters: web::Data<LevelFilters>,
+    namespace: Namespace,
+    mut payload: Multipart,
 ) -> impl Responder {
-    let db_lock = db.lock().unwrap();
-    let mut filtered: Vec<LogEntry> = db_lock.clone();
+    let max_bytes = cfg.import_max_mb.saturating_mul(1024 * 1024) as usize;
 
-    if let Some(service) = query.get("service") {
-        filtered.retain(|log| log.service == *service);
+    let mut upload: Option<Vec<u8>> = None;
+    while let Some(field) = payload.next().await {
+        let mut field = match field {
+            Ok(field) => field,
+            Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })),
+        };
+        let is_file = field.name() == Some("file");
+
+        // Other fields are drained and ignored
+        let mut body = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "error": e.to_string() })),
+            };
+            if is_file {
+                if body.len() + chunk.len() > max_bytes {
+                    return HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                        "error": "import too large",
+                        "import_max_mb": cfg.import_max_mb
+                    }));
+                }
+                body.extend_from_slice(&chunk);
+            }
+        }
+        if is_file {
+            upload = Some(body);
+        }
     }
-    if let Some(level) = query.get("level") {
-        filtered.retain(|log| log.level == *level);
+
+    let Some(upload) = upload else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "missing file field" }));
+    };
+
+    let mut accepted = Vec::new();
+    let mut errors = Vec::new();
+    let mut admitted = Vec::new();
+
+    for (i, line) in upload.split(|b| *b == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+
+        let mut entry = match serde_json::from_slice::<LogEntry>(line) {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(serde_json::json!({ "line": i + 1, "error": e.to_string() }));
+                continue;
+            }
+        };
+        message_limit.truncate(&mut entry);
+        if let Err(reason) = prepare_entry(&mut entry) {
+            errors.push(serde_json::json!({ "line": i + 1, "error": reason }));
+            continue;
+        }
+        // Neither accepted nor rejected, as in a batch
+        if level_filters.drops(&entry) {
+            continue;
+        }
+        match service_limit.admit(&entry.service) {
+            Admission::Full => {
+                errors.push(serde_json::json!({ "line": i + 1, "error": "service limit reached" }));
+                continue;
+            }
+            admission => admitted.push((entry.service.clone(), admission)),
+        }
+        pii.scrub(&mut entry);
+        namespace.assign(&mut entry);
+        accepted.push(entry);
     }
 
-    HttpResponse::Ok().json(filtered)
+    let accepted = match ingest_many(&storage, &seq, &dedup, &sinks, accepted).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            for (service, admission) in &admitted {
+                service_limit.release(service, *admission);
+            }
+            return storage_error(e);
+        }
+    };
+    HttpResponse::Ok().json(serde_json::json!({
+        "accepted": accepted.len(),
+        "rejected": errors.len(),
+        "errors": errors
+    }))
 }
 
-// GET /logs/stats
-async fn get_stats(db: web::Data<LogDb>) -> impl Responder {
-    let db_lock = db.lock().unwrap();
-    use std::collections::HashMap;
+// GET /logs?service=...&level=...&q=...|q_regex=...&since=...&until=...&offset=...&limit=...
+#[utoipa::path(
+    get,
+    path = "/v1/logs",
+    params(
+        FilterParams,
+        ("meta.{key}" = Option<String>, Query, description = "Match a metadata value by its string form"),
+        ("offset" = Option<usize>, Query, description = "Matches to skip, default 0"),
+        ("limit" = Option<usize>, Query, description = "Page size, default 100"),
+    ),
+    responses(
+        (status = 200, description = "One page of matching entries, oldest first", body = LogPage),
+        (status = 400, description = "Malformed filter", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_logs(
+    storage: web::Data<SharedStorage>,
+    cache: web::Data<QueryCache>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let params = match FilterParams::from_query(&query, &namespace).await {
+        Ok(params) => params,
+        Err(resp) => return resp,
+    };
 
-    let mut by_level: HashMap<String, usize> = HashMap::new();
-    let mut by_service: HashMap<String, usize> = HashMap::new();
+    let offset = query.get("offset").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+    let limit = query.get("limit").and_then(|v| v.parse::<usize>().ok()).unwrap_or(100);
 
-    for log in db_lock.iter() {
-        *by_level.entry(log.level.clone()).or_insert(0) += 1;
-        *by_service.entry(log.service.clone()).or_insert(0) += 1;
+    let cache_key = cache.key(&query, &namespace);
+    if let Some((logs, total)) = cache_key.as_deref().and_then(|key| cache.get(key)) {
+        return HttpResponse::Ok().json(serde_json::json!({
+            "total": total,
+            "offset": offset,
+            "limit": limit,
+            "logs": logs
+        }));
     }
 
-    let stats = serde_json::json!({
-        "by_level": by_level,
-        "by_service": by_service
-    });
+    // Only the requested page is cloned out of the store
+    let mut total = 0;
+    let mut logs: Vec<LogEntry> = Vec::new();
+    if let Err(e) = storage
+        .scan(&params, &mut |log| {
+            if total >= offset && logs.len() < limit {
+                logs.push(log.clone());
+            }
+            total += 1;
+        })
+        .await
+    {
+        return storage_error(e);
+    }
 
-    HttpResponse::Ok().json(stats)
-}
+    if let Some(key) = cache_key {
+        cache.put(key, logs.clone(), total);
+    }
 
-// Serve index.html
-async fn index() -> actix_web::Result<NamedFile> {
-    Ok(NamedFile::open("static/index.html")?)
+    HttpResponse::Ok().json(serde_json::json!({
+        "total": total,
+        "offset": offset,
+        "limit": limit,
+        "logs": logs
+    }))
 }
 
-// SSE: /logs/stream
-async fn logs_stream(bcast: web::Data<Broadcaster>) -> HttpResponse {
-    let rx = bcast.subscribe();
+// Entries serialised per gzip chunk written to the export stream
+const EXPORT_CHUNK_LEN: usize = 500;
 
-    let stream = futures::stream::unfold(rx, |mut rx| async {
-        match rx.recv().await {
-            Ok(msg) => {
-                let sse_frame = format!("data: {}\n\n", msg);
-                Some((Ok::<Bytes, std::io::Error>(Bytes::from(sse_frame)), rx))
+// GET /logs/export, the GET /logs filters applied to a gzipped NDJSON download
+#[utoipa::path(
+    get,
+    path = "/v1/logs/export",
+    params(
+        FilterParams,
+        ("meta.{key}" = Option<String>, Query, description = "Match a metadata value by its string form"),
+    ),
+    responses(
+        (status = 200, description = "Gzipped NDJSON, one entry per line", content_type = "application/x-ndjson", body = String),
+        (status = 400, description = "Malformed filter", body = ErrorResponse),
+    ),
+)]
+async fn export_logs(
+    storage: web::Data<SharedStorage>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let params = match FilterParams::from_query(&query, &namespace).await {
+        Ok(params) => params,
+        Err(resp) => return resp,
+    };
+
+    // The store is only locked while the matches are cloned, serialisation happens afterwards
+    let logs = match storage.query(&params).await {
+        Ok(logs) => logs,
+        Err(e) => return storage_error(e),
+    };
+
+    let encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let stream = futures::stream::unfold(Some((logs.into_iter().peekable(), encoder)), |state| async move {
+        let (mut logs, mut encoder) = state?;
+        if logs.peek().is_none() {
+            return Some((encoder.finish().map(Bytes::from), None));
+        }
+
+        for entry in logs.by_ref().take(EXPORT_CHUNK_LEN) {
+            let written = serde_json::to_writer(&mut encoder, &entry)
+                .map_err(std::io::Error::from)
+                .and_then(|()| encoder.write_all(b"\n"));
+            if let Err(e) = written {
+                return Some((Err(e), None));
             }
-            Err(_) => None,
         }
+        // Hand over whatever the encoder has produced so far
+        let bytes = Bytes::from(std::mem::take(encoder.get_mut()));
+        Some((Ok(bytes), Some((logs, encoder))))
     });
 
     HttpResponse::Ok()
-        .append_header(("Content-Type", "text/event-stream"))
-        .append_header(("Cache-Control", "no-cache"))
+        .append_header(("Content-Type", "application/x-ndjson"))
+        .append_header(("Content-Encoding", "gzip"))
+        .append_header(("Content-Disposition", "attachment; filename=\"logs.ndjson.gz\""))
         .streaming(stream)
 }
 
-// Metrics endpoint
-async fn metrics(registry: web::Data<Registry>) -> HttpResponse {
-    let encoder = TextEncoder::new();
-    let metric_families = registry.gather();
-    let mut buffer = Vec::new();
-    encoder.encode(&metric_families, &mut buffer).unwrap();
-    HttpResponse::Ok()
-        .append_header(("Content-Type", encoder.format_type()))
-        .body(buffer)
+// GET /logs/count, same filters as GET /logs without transferring the entries
+#[utoipa::path(
+    get,
+    path = "/v1/logs/count",
+    params(
+        FilterParams,
+        ("meta.{key}" = Option<String>, Query, description = "Match a metadata value by its string form"),
+    ),
+    responses(
+        (status = 200, description = "Number of matches", body = Count),
+        (status = 400, description = "Malformed filter", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_log_count(
+    storage: web::Data<SharedStorage>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let params = match FilterParams::from_query(&query, &namespace).await {
+        Ok(params) => params,
+        Err(resp) => return resp,
+    };
+
+    match storage.count(&params).await {
+        Ok(count) => HttpResponse::Ok().json(serde_json::json!({ "count": count })),
+        Err(e) => storage_error(e),
+    }
 }
 
-// Async persistence task
-async fn persist_logs(db: LogDb, cfg: LoggingConfig) {
-    loop {
-        sleep(Duration::from_secs(cfg.persist_interval_secs)).await;
+// GET /logs/tail?n=...&service=...&level=... (plus the other GET /logs filters)
+#[utoipa::path(
+    get,
+    path = "/v1/logs/tail",
+    params(
+        ("n" = Option<usize>, Query, description = "Entries to return, default 50, at most max_tail"),
+        FilterParams,
+        ("meta.{key}" = Option<String>, Query, description = "Match a metadata value by its string form"),
+    ),
+    responses(
+        (status = 200, description = "The last n matches, newest first", body = Vec<LogEntry>),
+        (status = 400, description = "Malformed filter", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_logs_tail(
+    storage: web::Data<SharedStorage>,
+    cfg: web::Data<LoggingConfig>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let n = match query.get("n").map(|v| v.parse::<usize>()) {
+        None => 50,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({ "error": "n must be a non-negative integer" }));
+        }
+    };
+    if n > cfg.max_tail {
+        return HttpResponse::BadRequest()
+            .json(serde_json::json!({ "error": format!("n must be at most {}", cfg.max_tail) }));
+    }
 
-        let logs = {
-            let db_lock = db.lock().unwrap();
-            db_lock.clone()
-        };
+    let params = match FilterParams::from_query(&query, &namespace).await {
+        Ok(params) => params,
+        Err(resp) => return resp,
+    };
 
-        if !logs.is_empty() {
-            let json = serde_json::to_string(&logs).unwrap();
-            let mut file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&cfg.file_path)
-                .unwrap();
-            file.write_all(json.as_bytes()).unwrap();
-        }
+    let mut tail: VecDeque<LogEntry> = VecDeque::with_capacity(n);
+    if let Err(e) = storage
+        .scan(&params, &mut |log| {
+            if n > 0 {
+                if tail.len() == n {
+                    tail.pop_front();
+                }
+                tail.push_back(log.clone());
+            }
+        })
+        .await
+    {
+        return storage_error(e);
     }
+
+    // Newest first
+    HttpResponse::Ok().json(tail.into_iter().rev().collect::<Vec<_>>())
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let cfg = load_config();
+// A storage failure is the server's fault; log the detail and keep it out of the response
+fn storage_error(e: StorageError) -> HttpResponse {
+    tracing::error!(error = %e, "storage error");
+    HttpResponse::InternalServerError().json(serde_json::json!({ "error": "storage unavailable" }))
+}
 
-    let db: LogDb = Arc::new(Mutex::new(Vec::new()));
-    let bcast: Broadcaster = Arc::new(broadcast::channel(100).0);
+// Check the Authorization: Bearer header against the configured admin token
+fn check_admin(req: &HttpRequest, cfg: &ServerConfig) -> Result<(), HttpResponse> {
+    let Some(expected) = cfg.admin_token.as_deref() else {
+        return Err(HttpResponse::Forbidden()
+            .json(serde_json::json!({ "error": "admin endpoints are disabled" })));
+    };
 
-    // Prometheus metrics
-    let registry = Registry::new();
-    let total_logs = IntCounter::new("total_logs", "Total number of logs received").unwrap();
-    registry.register(Box::new(total_logs.clone())).unwrap();
+    let provided = req
+        .headers()
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
 
-    // Spawn persistence task
-    let persist_db = db.clone();
-    let persist_cfg = cfg.logging.clone();
-    tokio::spawn(async move { persist_logs(persist_db, persist_cfg).await });
+    // Constant time, so the response time does not give the token away a byte at a time
+    if provided.is_some_and(|provided| secret_eq(expected, provided)) {
+        Ok(())
+    } else {
+        Err(HttpResponse::Unauthorized().json(serde_json::json!({ "error": "unauthorized" })))
+    }
+}
 
-    println!("Server running at http://{}:{}/", cfg.server.host, cfg.server.port);
+// DELETE /logs?service=...
+#[utoipa::path(
+    delete,
+    path = "/v1/logs",
+    params(
+        ("service" = Option<String>, Query, description = "Only clear this service"),
+    ),
+    responses(
+        (status = 204, description = "Cleared"),
+        (status = 401, description = "Wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin endpoints are disabled", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn clear_logs(
+    req: HttpRequest,
+    storage: web::Data<SharedStorage>,
+    seq: web::Data<LogSequence>,
+    server_cfg: web::Data<ServerConfig>,
+    persister: web::Data<Option<SharedPersister>>,
+    dedup: web::Data<Deduplicator>,
+    service_limit: web::Data<ServiceLimit>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> impl Responder {
+    if let Err(resp) = check_admin(&req, &server_cfg) {
+        return resp;
+    }
 
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(db.clone()))
-            .app_data(web::Data::new(bcast.clone()))
-            .app_data(web::Data::new(total_logs.clone()))
-            .app_data(web::Data::new(registry.clone()))
-            .route("/", web::get().to(index))
-            .route("/logs", web::post().to(post_log))
-            .route("/logs", web::get().to(get_logs))
-            .route("/logs/stats", web::get().to(get_stats))
-            .route("/logs/stream", web::get().to(logs_stream))
-            .route("/metrics", web::get().to(metrics))
-    })
-    .bind((cfg.server.host, cfg.server.port))?
-    .run()
-    .await
+    let service = query.get("service").map(String::as_str);
+    // Held throughout a full clear so no flush runs between emptying the store and its file
+    let mut persister = match (service, persister.get_ref()) {
+        (None, Some(persister)) => Some(persister.lock().await),
+        _ => None,
+    };
+    if let Err(e) = storage.clear(service).await {
+        return storage_error(e);
+    }
+    // A mapping left behind would merge later duplicates into whichever new entry reuses its id
+    dedup.clear();
+    service_limit.forget(service);
+    if service.is_none() {
+        seq.store(0, Ordering::Relaxed);
+    }
+    if let Some(persister) = persister.as_mut()
+        && let Err(e) = persister.reset().await
+    {
+        tracing::error!(error = %e, "failed to empty the persisted log file");
+        return HttpResponse::InternalServerError()
+            .json(serde_json::json!({ "error": "cleared in memory but not in the persisted file" }));
+    }
+
+    HttpResponse::NoContent().finish()
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct PurgeRequest {
+    service: Option<String>,
+    level: Option<String>,
+    // RFC 3339, exclusive
+    before: Option<String>,
+}
+
+// POST /logs/purge, removes the entries matching every given field
+#[utoipa::path(
+    post,
+    path = "/v1/logs/purge",
+    request_body = PurgeRequest,
+    responses(
+        (status = 200, description = "Entries removed", body = Purged),
+        (status = 400, description = "before is not RFC 3339", body = ErrorResponse),
+        (status = 401, description = "Wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin endpoints are disabled", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn purge_logs(
+    req: HttpRequest,
+    storage: web::Data<SharedStorage>,
+    server_cfg: web::Data<ServerConfig>,
+    dedup: web::Data<Deduplicator>,
+    service_limit: web::Data<ServiceLimit>,
+    body: web::Json<PurgeRequest>,
+) -> impl Responder {
+    if let Err(resp) = check_admin(&req, &server_cfg) {
+        return resp;
+    }
+
+    let body = body.into_inner();
+    let before = match body.before.as_deref().map(DateTime::parse_from_rfc3339) {
+        None => None,
+        Some(Ok(before)) => Some(before),
+        Some(Err(e)) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": format!("before must be an RFC 3339 timestamp: {}", e) }));
+        }
+    };
+    let params = FilterParams {
+        service: body.service,
+        level: body.level,
+        // until is inclusive, before is not
+        until: before.map(|before| before - TimeDelta::nanoseconds(1)),
+        ..Default::default()
+    };
+
+    match storage.purge(&params).await {
+        Ok(purged) => {
+            // Which fingerprints point at the purged entries is not known, so start over
+            if purged > 0 {
+                dedup.clear();
+                if let Err(e) = service_limit.reseed(&storage).await {
+                    return storage_error(e);
+                }
+            }
+            HttpResponse::Ok().json(serde_json::json!({ "purged": purged }))
+        }
+        Err(e) => storage_error(e),
+    }
+}
+
+// POST /logs/verify, recompute the signature of every line in the current persisted file
+#[utoipa::path(
+    post,
+    path = "/v1/logs/verify",
+    responses(
+        (status = 200, description = "Lines by outcome", body = Verified),
+        (status = 400, description = "No signing_key configured", body = ErrorResponse),
+        (status = 401, description = "Wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin endpoints are disabled", body = ErrorResponse),
+        (status = 500, description = "The file could not be read", body = ErrorResponse),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn verify_logs(
+    req: HttpRequest,
+    server_cfg: web::Data<ServerConfig>,
+    cfg: web::Data<LoggingConfig>,
+) -> impl Responder {
+    if let Err(resp) = check_admin(&req, &server_cfg) {
+        return resp;
+    }
+    let Some(key) = cfg.signing_key.as_deref() else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "no [logging] signing_key is configured" }));
+    };
+
+    let cipher = match line_cipher(&cfg) {
+        Ok(cipher) => cipher,
+        Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({ "error": e })),
+    };
+
+    let path = persisted_path(&cfg);
+    let content = match read_persisted_file(&path).await {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::error!(path, error = %e, "verify failed to read persisted file");
+            return HttpResponse::InternalServerError().json(serde_json::json!({ "error": "could not read persisted file" }));
+        }
+    };
+
+    let (mut ok, mut tampered, mut missing_sig) = (0, 0, 0);
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        // A line that no longer decrypts has been modified, or was written under another key
+        let Ok(line) = encryption::decode_line(cipher.as_ref(), line) else {
+            tampered += 1;
+            continue;
+        };
+        match signing::check_line(key, &line) {
+            signing::LineCheck::Ok => ok += 1,
+            signing::LineCheck::Tampered => tampered += 1,
+            signing::LineCheck::MissingSig => missing_sig += 1,
+        }
+    }
+    if tampered > 0 {
+        tracing::warn!(path, tampered, "persisted lines failed signature verification");
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({ "ok": ok, "tampered": tampered, "missing_sig": missing_sig }))
+}
+
+// GET /logs/{id}
+#[utoipa::path(
+    get,
+    path = "/v1/logs/{id}",
+    params(
+        ("id" = u64, Path),
+    ),
+    responses(
+        (status = 200, body = LogEntry),
+        (status = 404, description = "No entry with this id", body = LogNotFound),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_log_by_id(
+    storage: web::Data<SharedStorage>,
+    path: web::Path<u64>,
+    namespace: Namespace,
+) -> impl Responder {
+    let id = path.into_inner();
+
+    // Another namespace's entry is as good as missing
+    match storage.get(id).await {
+        Ok(Some(entry)) if namespace_of(&entry) == namespace.name => HttpResponse::Ok().json(entry),
+        Ok(_) => HttpResponse::NotFound().json(serde_json::json!({ "error": "log not found", "id": id })),
+        Err(e) => storage_error(e),
+    }
+}
+
+// PATCH /logs/{id} body; fields left out or null stay as they are
+#[derive(Debug, Deserialize, ToSchema)]
+struct LogPatch {
+    message: Option<String>,
+    // Merged into the entry's metadata, where a null value removes its key
+    #[schema(value_type = Option<Object>)]
+    metadata: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+impl LogPatch {
+    fn apply(&self, entry: &mut LogEntry) {
+        if let Some(message) = &self.message {
+            entry.message.clone_from(message);
+        }
+        if let Some(patch) = &self.metadata {
+            let metadata = entry.metadata.get_or_insert_default();
+            for (key, value) in patch {
+                if value.is_null() {
+                    metadata.remove(key);
+                } else {
+                    metadata.insert(key.clone(), value.clone());
+                }
+            }
+            if metadata.is_empty() {
+                entry.metadata = None;
+            }
+        }
+    }
+}
+
+// PATCH /logs/{id}, e.g. to note how an error was resolved; subscribers get the entry as an update.
+// memory_file only changes the in-memory copy, a line already persisted is not rewritten
+#[utoipa::path(
+    patch,
+    path = "/v1/logs/{id}",
+    params(
+        ("id" = u64, Path),
+    ),
+    request_body = LogPatch,
+    responses(
+        (status = 200, description = "The updated entry", body = LogEntry),
+        (status = 400, description = "Message too large", body = LimitExceeded),
+        (status = 401, description = "Wrong admin token", body = ErrorResponse),
+        (status = 403, description = "Admin endpoints are disabled", body = ErrorResponse),
+        (status = 404, description = "No entry with this id", body = LogNotFound),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn patch_log(
+    req: HttpRequest,
+    storage: web::Data<SharedStorage>,
+    server_cfg: web::Data<ServerConfig>,
+    message_limit: web::Data<MessageLimit>,
+    pii: web::Data<PiiScrubber>,
+    sinks: web::Data<Sinks>,
+    path: web::Path<u64>,
+    body: web::Json<LogPatch>,
+    namespace: Namespace,
+) -> impl Responder {
+    if let Err(resp) = check_admin(&req, &server_cfg) {
+        return resp;
+    }
+    let id = path.into_inner();
+    let patch = body.into_inner();
+
+    match storage.get(id).await {
+        Ok(Some(mut entry)) if namespace_of(&entry) == namespace.name => {
+            patch.apply(&mut entry);
+            if let Err(resp) = message_limit.check(&entry) {
+                return resp;
+            }
+        }
+        Ok(_) => return HttpResponse::NotFound().json(serde_json::json!({ "error": "log not found", "id": id })),
+        Err(e) => return storage_error(e),
+    }
+
+    // Scrubbed like anything ingested; the rest of the entry was scrubbed already
+    let apply = |entry: &mut LogEntry| {
+        patch.apply(entry);
+        pii.scrub(entry);
+    };
+    match storage.update(id, &apply).await {
+        Ok(Some(entry)) => {
+            sinks.stored(&entry, true);
+            HttpResponse::Ok().json(entry)
+        }
+        Ok(None) => HttpResponse::NotFound().json(serde_json::json!({ "error": "log not found", "id": id })),
+        Err(e) => storage_error(e),
+    }
+}
+
+// DELETE /logs/{id}
+// Not broadcast over SSE and does not touch the total_logs counter
+#[utoipa::path(
+    delete,
+    path = "/v1/logs/{id}",
+    params(
+        ("id" = u64, Path),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Missing or wrong X-API-Key", body = ErrorResponse),
+        (status = 404, description = "No entry with this id", body = LogNotFound),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+    security((), ("api_key" = [])),
+)]
+async fn delete_log(
+    storage: web::Data<SharedStorage>,
+    path: web::Path<u64>,
+    dedup: web::Data<Deduplicator>,
+    namespace: Namespace,
+) -> impl Responder {
+    let id = path.into_inner();
+
+    match storage.get(id).await {
+        Ok(Some(entry)) if namespace_of(&entry) == namespace.name => {}
+        Ok(_) => return HttpResponse::NotFound().json(serde_json::json!({ "error": "log not found", "id": id })),
+        Err(e) => return storage_error(e),
+    }
+    match storage.delete(id).await {
+        Ok(true) => {
+            dedup.forget_id(id);
+            HttpResponse::NoContent().finish()
+        }
+        Ok(false) => HttpResponse::NotFound().json(serde_json::json!({ "error": "log not found", "id": id })),
+        Err(e) => storage_error(e),
+    }
+}
+
+// GET /logs/trace/{trace_id}, every service's entries for one trace, oldest first
+#[utoipa::path(
+    get,
+    path = "/v1/logs/trace/{trace_id}",
+    params(
+        ("trace_id" = String, Path),
+    ),
+    responses(
+        (status = 200, description = "The trace's entries, oldest first", body = Vec<LogEntry>),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_logs_by_trace(
+    storage: web::Data<SharedStorage>,
+    path: web::Path<String>,
+    namespace: Namespace,
+) -> impl Responder {
+    let params = FilterParams {
+        trace_id: Some(path.into_inner()),
+        namespace: Some(namespace.name),
+        ..Default::default()
+    };
+
+    let mut logs = match storage.query(&params).await {
+        Ok(logs) => logs,
+        Err(e) => return storage_error(e),
+    };
+    // Unparseable timestamps sort first; the sort is stable so insertion order breaks ties
+    logs.sort_by_key(|log| DateTime::parse_from_rfc3339(&log.timestamp).ok());
+
+    HttpResponse::Ok().json(logs)
+}
+
+// GET /logs/service_graph, caller -> callee edges guessed from which service shows up first in each trace
+#[utoipa::path(
+    get,
+    path = "/v1/logs/service_graph",
+    responses(
+        (status = 200, description = "Services seen in traces and the edges inferred between them", body = ServiceGraph),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_service_graph(storage: web::Data<SharedStorage>, namespace: Namespace) -> impl Responder {
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    // trace_id -> service -> timestamp of the service's first entry in the trace
+    let mut traces: HashMap<String, HashMap<String, DateTime<FixedOffset>>> = HashMap::new();
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+    if let Err(e) = storage
+        .scan(&scope, &mut |log| {
+            let Some(trace_id) = &log.trace_id else {
+                return;
+            };
+            let Ok(ts) = DateTime::parse_from_rfc3339(&log.timestamp) else {
+                return;
+            };
+            let first = traces.entry(trace_id.clone()).or_default().entry(log.service.clone()).or_insert(ts);
+            *first = (*first).min(ts);
+        })
+        .await
+    {
+        return storage_error(e);
+    }
+
+    let mut nodes: BTreeSet<String> = BTreeSet::new();
+    let mut edges: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for services in traces.values() {
+        nodes.extend(services.keys().cloned());
+        for (from, from_ts) in services {
+            for (to, to_ts) in services {
+                if from_ts < to_ts {
+                    *edges.entry((from.clone(), to.clone())).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    let edges: Vec<_> = edges
+        .into_iter()
+        .map(|((from, to), count)| serde_json::json!({ "from": from, "to": to, "count": count }))
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({
+        "nodes": nodes,
+        "edges": edges,
+        "note": "edges are inferred from the order of each service's first entry within a trace; clock skew between services and concurrent calls make them approximate",
+    }))
+}
+
+// Most entries GET /logs/similar/{id} returns
+const MAX_SIMILAR: usize = 20;
+
+// GET /logs/similar/{id}, entries whose message shares word pairs with this one's, by Jaccard score
+#[utoipa::path(
+    get,
+    path = "/v1/logs/similar/{id}",
+    params(("id" = u64, Path)),
+    responses(
+        (status = 200, description = "Entries scoring above similarity_threshold, best first", body = SimilarLogs),
+        (status = 404, description = "No entry with this id", body = LogNotFound),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_similar_logs(
+    storage: web::Data<SharedStorage>,
+    cfg: web::Data<LoggingConfig>,
+    path: web::Path<u64>,
+    namespace: Namespace,
+) -> impl Responder {
+    let id = path.into_inner();
+    let reference = match storage.get(id).await {
+        Ok(Some(entry)) if namespace_of(&entry) == namespace.name => entry,
+        Ok(_) => return HttpResponse::NotFound().json(serde_json::json!({ "error": "log not found", "id": id })),
+        Err(e) => return storage_error(e),
+    };
+
+    // TODO: index shingles (MinHash/LSH) instead of scoring every entry in the namespace
+    let wanted = similarity::shingles(&reference.message);
+    let mut similar: Vec<(f64, LogEntry)> = Vec::new();
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+    if let Err(e) = storage
+        .scan(&scope, &mut |log| {
+            if log.id == id {
+                return;
+            }
+            let score = similarity::jaccard(&wanted, &similarity::shingles(&log.message));
+            if score > cfg.similarity_threshold {
+                similar.push((score, log.clone()));
+            }
+        })
+        .await
+    {
+        return storage_error(e);
+    }
+
+    // Ties go to the newer entry
+    similar.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.id.cmp(&a.1.id)));
+    let similar: Vec<_> = similar
+        .into_iter()
+        .take(MAX_SIMILAR)
+        .map(|(score, entry)| serde_json::json!({ "entry": entry, "score": score }))
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({ "reference": reference, "similar": similar }))
+}
+
+// GET /logs/stats?window=minute|hour|day
+#[utoipa::path(
+    get,
+    path = "/v1/logs/stats",
+    params(
+        ("window" = Option<String>, Query, description = "minute, hour or day to bucket by time"),
+    ),
+    responses(
+        (status = 200, description = "Totals, or per-level buckets when window is given", body = StatsResponse),
+        (status = 400, description = "Unknown window", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_stats(
+    storage: web::Data<SharedStorage>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    use std::collections::{BTreeMap, HashMap};
+
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+
+    if let Some(window) = query.get("window") {
+        let granularity = match window.as_str() {
+            "minute" => TimeDelta::minutes(1),
+            "hour" => TimeDelta::hours(1),
+            "day" => TimeDelta::days(1),
+            _ => {
+                return HttpResponse::BadRequest()
+                    .json(serde_json::json!({ "error": "window must be one of minute, hour, day" }));
+            }
+        };
+
+        let mut buckets: BTreeMap<DateTime<Utc>, HashMap<String, usize>> = BTreeMap::new();
+        if let Err(e) = storage
+            .scan(&scope, &mut |log| {
+                let start = match DateTime::parse_from_rfc3339(&log.timestamp)
+                    .map_err(|e| e.to_string())
+                    .and_then(|ts| ts.with_timezone(&Utc).duration_trunc(granularity).map_err(|e| e.to_string()))
+                {
+                    Ok(start) => start,
+                    Err(e) => {
+                        tracing::warn!(id = log.id, timestamp = %log.timestamp, error = %e, "skipping log in stats, bad timestamp");
+                        return;
+                    }
+                };
+                *buckets.entry(start).or_default().entry(log.level.clone()).or_insert(0) += 1;
+            })
+            .await
+        {
+            return storage_error(e);
+        }
+
+        let buckets: Vec<_> = buckets
+            .into_iter()
+            .map(|(start, by_level)| serde_json::json!({ "start": start.to_rfc3339(), "by_level": by_level }))
+            .collect();
+        return HttpResponse::Ok().json(serde_json::json!({ "buckets": buckets }));
+    }
+
+    let mut by_level: HashMap<String, usize> = HashMap::new();
+    let mut by_service: HashMap<String, usize> = HashMap::new();
+
+    if let Err(e) = storage
+        .scan(&scope, &mut |log| {
+            *by_level.entry(log.level.clone()).or_insert(0) += 1;
+            *by_service.entry(log.service.clone()).or_insert(0) += 1;
+        })
+        .await
+    {
+        return storage_error(e);
+    }
+
+    let stats = serde_json::json!({
+        "by_level": by_level,
+        "by_service": by_service
+    });
+
+    HttpResponse::Ok().json(stats)
+}
+
+// Entry counts in one window of GET /logs/diff
+#[derive(Default, Serialize)]
+struct WindowVolume {
+    total: usize,
+    by_level: std::collections::HashMap<String, usize>,
+    by_service: std::collections::HashMap<String, usize>,
+}
+
+impl WindowVolume {
+    async fn count(storage: &SharedStorage, scope: &FilterParams) -> Result<Self, StorageError> {
+        let mut volume = WindowVolume::default();
+        storage
+            .scan(scope, &mut |log| {
+                volume.total += 1;
+                *volume.by_level.entry(log.level.clone()).or_insert(0) += 1;
+                *volume.by_service.entry(log.service.clone()).or_insert(0) += 1;
+            })
+            .await?;
+        Ok(volume)
+    }
+}
+
+// current minus baseline for every key seen in either window
+fn count_deltas(
+    baseline: &std::collections::HashMap<String, usize>,
+    current: &std::collections::HashMap<String, usize>,
+) -> std::collections::BTreeMap<String, i64> {
+    baseline
+        .keys()
+        .chain(current.keys())
+        .map(|key| {
+            let before = baseline.get(key).copied().unwrap_or(0) as i64;
+            let after = current.get(key).copied().unwrap_or(0) as i64;
+            (key.clone(), after - before)
+        })
+        .collect()
+}
+
+// GET /logs/diff?baseline_start=..&baseline_end=..&current_start=..&current_end=..
+#[utoipa::path(
+    get,
+    path = "/v1/logs/diff",
+    params(
+        ("baseline_start" = String, Query, description = "RFC 3339"),
+        ("baseline_end" = String, Query, description = "RFC 3339"),
+        ("current_start" = String, Query, description = "RFC 3339"),
+        ("current_end" = String, Query, description = "RFC 3339"),
+    ),
+    responses(
+        (status = 200, description = "Counts in both windows and current minus baseline", body = LogDiff),
+        (status = 400, description = "A window bound is missing or malformed", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_logs_diff(
+    storage: web::Data<SharedStorage>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let mut windows = Vec::with_capacity(2);
+    for window in ["baseline", "current"] {
+        let mut bounds = Vec::with_capacity(2);
+        for bound in ["start", "end"] {
+            let name = format!("{window}_{bound}");
+            match filters::parse_time_param(&query, &name) {
+                Ok(Some(ts)) => bounds.push(ts),
+                Ok(None) => {
+                    return HttpResponse::BadRequest().json(serde_json::json!({ "error": format!("missing {name}") }));
+                }
+                Err(resp) => return resp,
+            }
+        }
+        if bounds[1] < bounds[0] {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": format!("{window}_end must not be earlier than {window}_start") }));
+        }
+        windows.push(FilterParams {
+            namespace: Some(namespace.name.clone()),
+            since: Some(bounds[0]),
+            until: Some(bounds[1]),
+            ..Default::default()
+        });
+    }
+
+    let (baseline, current) = match tokio::try_join!(
+        WindowVolume::count(&storage, &windows[0]),
+        WindowVolume::count(&storage, &windows[1]),
+    ) {
+        Ok(volumes) => volumes,
+        Err(e) => return storage_error(e),
+    };
+
+    let diff = serde_json::json!({
+        "total_delta": current.total as i64 - baseline.total as i64,
+        "by_level": count_deltas(&baseline.by_level, &current.by_level),
+        "by_service": count_deltas(&baseline.by_service, &current.by_service),
+    });
+    HttpResponse::Ok().json(serde_json::json!({ "baseline": baseline, "current": current, "diff": diff }))
+}
+
+// GET /health
+#[utoipa::path(
+    get,
+    path = "/v1/health",
+    responses(
+        (status = 200, body = Health),
+    ),
+)]
+async fn health(app_cfg: web::Data<AppConfig>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "uptime_secs": app_cfg.started_at.elapsed().as_secs()
+    }))
+}
+
+// GET /ready
+#[utoipa::path(
+    get,
+    path = "/v1/ready",
+    responses(
+        (status = 200, body = Ready),
+        (status = 503, description = "Persistence not yet confirmed", body = NotReady),
+    ),
+)]
+async fn ready(persist_ready: web::Data<PersistReady>) -> impl Responder {
+    if persist_ready.load(Ordering::Relaxed) {
+        HttpResponse::Ok().json(serde_json::json!({ "status": "ready" }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "status": "not_ready",
+            "reason": "persistence not yet confirmed"
+        }))
+    }
+}
+
+// Count matching entries per distinct key, most frequent first; entries without a key are skipped
+async fn count_by(
+    storage: &SharedStorage,
+    filters: &FilterParams,
+    key: impl Fn(&LogEntry) -> Option<String> + Sync,
+) -> Result<Vec<(String, usize)>, StorageError> {
+    let mut counts: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    storage
+        .scan(filters, &mut |log| {
+            if let Some(key) = key(log) {
+                *counts.entry(key).or_insert(0) += 1;
+            }
+        })
+        .await?;
+
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(counts)
+}
+
+fn named_counts(counts: Vec<(String, usize)>) -> Vec<serde_json::Value> {
+    counts
+        .into_iter()
+        .map(|(name, count)| serde_json::json!({ "name": name, "count": count }))
+        .collect()
+}
+
+// GET /logs/services
+#[utoipa::path(
+    get,
+    path = "/v1/logs/services",
+    responses(
+        (status = 200, description = "Entry count per service, most first", body = Services),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_services(storage: web::Data<SharedStorage>, namespace: Namespace) -> impl Responder {
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+    let counts = match count_by(&storage, &scope, |log| Some(log.service.clone())).await {
+        Ok(counts) => counts,
+        Err(e) => return storage_error(e),
+    };
+    HttpResponse::Ok().json(serde_json::json!({ "services": named_counts(counts) }))
+}
+
+// GET /logs/levels
+#[utoipa::path(
+    get,
+    path = "/v1/logs/levels",
+    responses(
+        (status = 200, description = "Entry count per level, most first", body = Levels),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_levels(storage: web::Data<SharedStorage>, namespace: Namespace) -> impl Responder {
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+    let counts = match count_by(&storage, &scope, |log| Some(log.level.clone())).await {
+        Ok(counts) => counts,
+        Err(e) => return storage_error(e),
+    };
+    HttpResponse::Ok().json(serde_json::json!({ "levels": named_counts(counts) }))
+}
+
+// JSON type names GET /logs/fields reports, in the order ties between them are broken
+const FIELD_TYPES: [&str; 6] = ["string", "number", "boolean", "object", "array", "null"];
+
+fn json_type(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::String(_) => 0,
+        serde_json::Value::Number(_) => 1,
+        serde_json::Value::Bool(_) => 2,
+        serde_json::Value::Object(_) => 3,
+        serde_json::Value::Array(_) => 4,
+        serde_json::Value::Null => 5,
+    }
+}
+
+// One metadata key across the scanned entries: how often each type occurred, and the first value of each
+#[derive(Default)]
+struct FieldSeen {
+    count: usize,
+    by_type: [usize; FIELD_TYPES.len()],
+    examples: [Option<serde_json::Value>; FIELD_TYPES.len()],
+}
+
+// GET /logs/fields
+#[utoipa::path(
+    get,
+    path = "/v1/logs/fields",
+    responses(
+        (status = 200, description = "Every metadata key in stored entries, alphabetically", body = Fields,
+            headers(("Cache-Control" = String, description = "max-age=30"))),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_fields(storage: web::Data<SharedStorage>, namespace: Namespace) -> impl Responder {
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+    let mut fields: std::collections::BTreeMap<String, FieldSeen> = std::collections::BTreeMap::new();
+    let scanned = storage
+        .scan(&scope, &mut |log| {
+            let Some(metadata) = &log.metadata else {
+                return;
+            };
+            for (key, value) in metadata {
+                let seen = match fields.get_mut(key) {
+                    Some(seen) => seen,
+                    None => fields.entry(key.clone()).or_default(),
+                };
+                let ty = json_type(value);
+                seen.count += 1;
+                seen.by_type[ty] += 1;
+                seen.examples[ty].get_or_insert_with(|| value.clone());
+            }
+        })
+        .await;
+    if let Err(e) = scanned {
+        return storage_error(e);
+    }
+
+    let keys: Vec<serde_json::Value> = fields
+        .into_iter()
+        .map(|(key, mut seen)| {
+            // max_by_key keeps the last of equal counts, so reverse to let FIELD_TYPES order win ties
+            let ty = (0..FIELD_TYPES.len()).rev().max_by_key(|&i| seen.by_type[i]).unwrap_or(0);
+            serde_json::json!({
+                "key": key,
+                "type": FIELD_TYPES[ty],
+                "example": seen.examples[ty].take(),
+                "count": seen.count,
+            })
+        })
+        .collect();
+    // A full scan per call; clients polling for new keys should not need to come back sooner
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "max-age=30"))
+        .json(serde_json::json!({ "metadata_keys": keys }))
+}
+
+// GET /logs/aggregate?group_by=service|level|meta.<key> (plus the GET /logs filters)
+#[utoipa::path(
+    get,
+    path = "/v1/logs/aggregate",
+    params(
+        ("group_by" = String, Query, description = "service, level or meta.<key>"),
+        FilterParams,
+        ("meta.{key}" = Option<String>, Query, description = "Match a metadata value by its string form"),
+    ),
+    responses(
+        (status = 200, description = "Match count per group, most first", body = Vec<AggregateGroup>),
+        (status = 400, description = "Missing or unknown group_by, or a malformed filter", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_logs_aggregate(
+    storage: web::Data<SharedStorage>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let Some(group_by) = query.get("group_by") else {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "error": "group_by is required" }));
+    };
+    let meta_key = group_by.strip_prefix("meta.");
+    if meta_key.is_none_or(str::is_empty) && !matches!(group_by.as_str(), "service" | "level") {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("cannot group by {:?}, expected service, level or meta.<key>", group_by)
+        }));
+    }
+
+    let params = match FilterParams::from_query(&query, &namespace).await {
+        Ok(params) => params,
+        Err(resp) => return resp,
+    };
+
+    let counts = count_by(&storage, &params, |log| match (group_by.as_str(), meta_key) {
+        (_, Some(key)) => log.metadata.as_ref()?.get(key).map(filters::meta_value_string),
+        ("service", None) => Some(log.service.clone()),
+        _ => Some(log.level.clone()),
+    })
+    .await;
+    let counts = match counts {
+        Ok(counts) => counts,
+        Err(e) => return storage_error(e),
+    };
+
+    let groups: Vec<_> = counts
+        .into_iter()
+        .map(|(value, count)| serde_json::json!({ "value": value, "count": count }))
+        .collect();
+    HttpResponse::Ok().json(groups)
+}
+
+// Upper bound for n on GET /logs/top_errors
+const MAX_TOP_ERRORS: usize = 100;
+
+#[derive(Debug, Default)]
+struct ErrorGroup {
+    count: usize,
+    service: String,
+    last_seen: String,
+}
+
+// GET /logs/top_errors?n=...&service=..., ERROR entries grouped by exact message
+#[utoipa::path(
+    get,
+    path = "/v1/logs/top_errors",
+    params(
+        ("n" = Option<usize>, Query, maximum = 100, description = "Groups to return, default 10"),
+        ("service" = Option<String>, Query),
+    ),
+    responses(
+        (status = 200, description = "ERROR messages by occurrences, most first", body = Vec<TopError>),
+        (status = 400, description = "n out of range", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_top_errors(
+    storage: web::Data<SharedStorage>,
+    top_error_count: web::Data<IntGauge>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let n = match query.get("n").map(|v| v.parse::<usize>()) {
+        None => 10,
+        Some(Ok(n)) if n <= MAX_TOP_ERRORS => n,
+        Some(_) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": format!("n must be an integer of at most {}", MAX_TOP_ERRORS) }));
+        }
+    };
+    let service = query.get("service");
+
+    // Every service and namespace is counted too, so the gauge does not depend on this request
+    let mut all: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut groups: std::collections::HashMap<String, ErrorGroup> = std::collections::HashMap::new();
+    let params = FilterParams { level: Some("ERROR".to_string()), ..Default::default() };
+    if let Err(e) = storage
+        .scan(&params, &mut |log| {
+            *all.entry(log.message.clone()).or_insert(0) += log.count;
+            if service.is_none_or(|s| log.service == *s) && namespace_of(log) == namespace.name {
+                let group = groups.entry(log.message.clone()).or_default();
+                group.count += log.count;
+                // Entries arrive in roughly chronological order, so the latest one wins
+                group.service.clone_from(&log.service);
+                group.last_seen.clone_from(&log.timestamp);
+            }
+        })
+        .await
+    {
+        return storage_error(e);
+    }
+    top_error_count.set(all.values().copied().max().unwrap_or(0) as i64);
+
+    let mut groups: Vec<(String, ErrorGroup)> = groups.into_iter().collect();
+    groups.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(&b.0)));
+    let top: Vec<_> = groups
+        .into_iter()
+        .take(n)
+        .map(|(message, group)| {
+            serde_json::json!({
+                "message": message,
+                "count": group.count,
+                "service": group.service,
+                "last_seen": group.last_seen
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(top)
+}
+
+// Upper bound for window_mins on GET /logs/error_rate, one day
+const MAX_ERROR_RATE_WINDOW_MINS: i64 = 1440;
+
+// GET /logs/error_rate?service=...&window_mins=..., one bucket per minute, oldest first
+#[utoipa::path(
+    get,
+    path = "/v1/logs/error_rate",
+    params(
+        ("service" = Option<String>, Query),
+        ("window_mins" = Option<i64>, Query, minimum = 1, maximum = 1440, description = "Minutes to cover, default 60"),
+    ),
+    responses(
+        (status = 200, description = "One point per minute, oldest first", body = Vec<ErrorRatePoint>),
+        (status = 400, description = "window_mins out of range", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_error_rate(
+    storage: web::Data<SharedStorage>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let window_mins = match query.get("window_mins").map(|v| v.parse::<i64>()) {
+        None => 60,
+        Some(Ok(n)) if (1..=MAX_ERROR_RATE_WINDOW_MINS).contains(&n) => n,
+        Some(_) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("window_mins must be between 1 and {}", MAX_ERROR_RATE_WINDOW_MINS)
+            }));
+        }
+    };
+
+    // The current, still filling minute is the last bucket
+    let Ok(current) = Utc::now().duration_trunc(TimeDelta::minutes(1)) else {
+        return HttpResponse::InternalServerError().finish();
+    };
+    let first = current - TimeDelta::minutes(window_mins - 1);
+
+    // (errors, total) per minute offset from first
+    let mut buckets = vec![(0usize, 0usize); window_mins as usize];
+    let params = FilterParams {
+        service: query.get("service").cloned(),
+        since: Some(first.fixed_offset()),
+        namespace: Some(namespace.name),
+        ..Default::default()
+    };
+    // TODO: cache parsed timestamps instead of parsing every entry on each request
+    if let Err(e) = storage
+        .scan(&params, &mut |log| {
+            let Ok(ts) = DateTime::parse_from_rfc3339(&log.timestamp) else {
+                return;
+            };
+            let offset = (ts.with_timezone(&Utc) - first).num_minutes();
+            if let Some(bucket) = usize::try_from(offset).ok().and_then(|i| buckets.get_mut(i)) {
+                bucket.1 += log.count;
+                if log.level == "ERROR" {
+                    bucket.0 += log.count;
+                }
+            }
+        })
+        .await
+    {
+        return storage_error(e);
+    }
+
+    let points: Vec<_> = buckets
+        .into_iter()
+        .enumerate()
+        .map(|(i, (errors, total))| {
+            let rate = if total == 0 { 0.0 } else { errors as f64 / total as f64 };
+            serde_json::json!({
+                "minute": (first + TimeDelta::minutes(i as i64)).to_rfc3339(),
+                "error_count": errors,
+                "total_count": total,
+                "rate": rate
+            })
+        })
+        .collect();
+
+    HttpResponse::Ok().json(points)
+}
+
+// GET /logs/service_health?window_secs=..., 1 - error_count / total per known service
+#[utoipa::path(
+    get,
+    path = "/v1/logs/service_health",
+    params(
+        ("window_secs" = Option<u32>, Query, description = "Seconds to cover, default 300"),
+    ),
+    responses(
+        (status = 200, description = "Health per known service", body = Vec<ServiceHealth>),
+        (status = 400, description = "Malformed window_secs", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn get_service_health(
+    storage: web::Data<SharedStorage>,
+    cfg: web::Data<LoggingConfig>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> impl Responder {
+    let window_secs = match query.get("window_secs").map(|v| v.parse::<u32>()) {
+        None => 300,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": "window_secs must be a non-negative integer" }));
+        }
+    };
+    let since = Utc::now() - TimeDelta::seconds(window_secs.into());
+
+    // Every service in the namespace is listed, even without traffic in the window
+    let scope = FilterParams { namespace: Some(namespace.name), ..Default::default() };
+    let mut counts: std::collections::BTreeMap<String, (usize, usize)> = std::collections::BTreeMap::new();
+    if let Err(e) = storage
+        .scan(&scope, &mut |log| {
+            let (errors, total) = counts.entry(log.service.clone()).or_default();
+            if DateTime::parse_from_rfc3339(&log.timestamp).is_ok_and(|ts| ts >= since) {
+                *total += log.count;
+                if log.level == "ERROR" {
+                    *errors += log.count;
+                }
+            }
+        })
+        .await
+    {
+        return storage_error(e);
+    }
+
+    let services: Vec<_> = counts
+        .into_iter()
+        .map(|(service, (errors, total))| {
+            // A service silent for the whole window scores 0.0, with status "idle" to tell it from a degraded one
+            let health = if total == 0 { 0.0 } else { 1.0 - errors as f64 / total as f64 };
+            let mut entry = serde_json::json!({
+                "service": service,
+                "health": health,
+                "error_count": errors,
+                "total": total,
+                "window_secs": window_secs
+            });
+            if total == 0 {
+                entry["status"] = "idle".into();
+            } else if health < cfg.health_warn_threshold {
+                entry["status"] = "degraded".into();
+            }
+            entry
+        })
+        .collect();
+
+    HttpResponse::Ok().json(services)
+}
+
+// The current API version, which every route but the dashboard and its docs lives under
+const API_PREFIX: &str = "/v1";
+
+// 308 from a pre-versioning path to its API_PREFIX equivalent, keeping the method, body and query
+async fn redirect_to_versioned(req: HttpRequest) -> HttpResponse {
+    let target = req.uri().path_and_query().map_or(req.path(), |pq| pq.as_str());
+    HttpResponse::PermanentRedirect()
+        .append_header((header::LOCATION, format!("{}{}", API_PREFIX, target)))
+        .finish()
+}
+
+// How long in-flight requests and the ingest queue get to finish once shutdown starts
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+// Stop the server on SIGTERM or Ctrl+C; in-flight requests get SHUTDOWN_GRACE to finish
+async fn stop_on_signal(handle: actix_web::dev::ServerHandle) {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "cannot listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    let signal = tokio::select! {
+        _ = terminate => "SIGTERM",
+        _ = tokio::signal::ctrl_c() => "SIGINT",
+    };
+    tracing::info!(signal, "shutting down");
+    handle.stop(true).await;
+}
+
+// Most entries GET /logs/stream?replay_last_n=... sends ahead of the live feed
+const MAX_STREAM_REPLAY: usize = 1000;
+
+// Serve index.html
+async fn index() -> actix_web::Result<NamedFile> {
+    Ok(NamedFile::open("static/index.html")?)
+}
+
+// SSE: /logs/stream?room=...&service=...&level=... (plus the other GET /logs filters)
+// room is "global" for every entry, or a service name for just that service's entries.
+// Frames carry the entry id, so a reconnecting client's Last-Event-ID replays what it missed.
+// replay_last_n sends the latest matches first as historical frames, unless Last-Event-ID is set
+#[utoipa::path(
+    get,
+    path = "/v1/logs/stream",
+    params(
+        ("room" = Option<String>, Query, description = "global, or a service name for only its entries"),
+        ("Last-Event-ID" = Option<u64>, Header, description = "Replay stored matches after this id first"),
+        ("replay_last_n" = Option<usize>, Query, description = "Send the last n stored matches as event: historical frames first, at most 1000"),
+        FilterParams,
+        ("meta.{key}" = Option<String>, Query, description = "Match a metadata value by its string form"),
+    ),
+    responses(
+        (status = 200, description = "Server-sent events, one entry per data frame", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Malformed filter or replay_last_n", body = ErrorResponse),
+        (status = 500, description = "Storage unavailable", body = ErrorResponse),
+    ),
+)]
+async fn logs_stream(
+    req: HttpRequest,
+    storage: web::Data<SharedStorage>,
+    bcast: web::Data<Broadcaster>,
+    rooms: web::Data<Rooms>,
+    server_cfg: web::Data<ServerConfig>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+    namespace: Namespace,
+) -> HttpResponse {
+    // Subscribe before reading the backlog so nothing falls in between
+    let room = query.get("room").map_or(GLOBAL_ROOM, String::as_str);
+    let rx = if room == GLOBAL_ROOM { bcast.subscribe() } else { rooms.subscribe(room) };
+
+    let params = match FilterParams::from_query(&query, &namespace).await {
+        Ok(params) => params,
+        Err(resp) => return resp,
+    };
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+
+    let replay_last_n = match query.get("replay_last_n").map(|v| v.parse::<usize>()) {
+        None => None,
+        Some(Ok(n)) => Some(n.min(MAX_STREAM_REPLAY)),
+        Some(Err(_)) => {
+            return HttpResponse::BadRequest()
+                .json(serde_json::json!({ "error": "replay_last_n must be a non-negative integer" }));
+        }
+    };
+
+    let mut replay = Vec::new();
+    let mut floor = 0;
+    if let Some(last_id) = last_event_id {
+        if let Err(e) = storage
+            .scan(&params, &mut |entry| {
+                if entry.id > last_id
+                    && let Ok(payload) = serde_json::to_string(entry)
+                {
+                    replay.push(Ok::<Bytes, std::io::Error>(Bytes::from(sse_frame(entry.id, &payload))));
+                    floor = entry.id;
+                }
+            })
+            .await
+        {
+            return storage_error(e);
+        }
+    } else if let Some(n) = replay_last_n.filter(|&n| n > 0) {
+        let mut history: VecDeque<LogEntry> = VecDeque::with_capacity(n);
+        if let Err(e) = storage
+            .scan(&params, &mut |entry| {
+                if history.len() == n {
+                    history.pop_front();
+                }
+                history.push_back(entry.clone());
+            })
+            .await
+        {
+            return storage_error(e);
+        }
+        // Oldest first; without an id, so a reconnect resumes from the last live frame instead
+        for entry in history {
+            if let Ok(payload) = serde_json::to_string(&entry) {
+                let frame = format!("event: historical\ndata: {}\n\n", payload);
+                replay.push(Ok::<Bytes, std::io::Error>(Bytes::from(frame)));
+                floor = entry.id;
+            }
+        }
+    }
+
+    let live = futures::stream::unfold(rx, move |mut rx| {
+        let params = params.clone();
+        async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        let entry = match serde_json::from_str::<LogEntry>(&event.payload) {
+                            Ok(entry) => entry,
+                            Err(e) => {
+                                tracing::warn!(error = %e, "logs_stream skipping undecodable broadcast payload");
+                                continue;
+                            }
+                        };
+                        if !params.matches(&entry) {
+                            continue;
+                        }
+                        // Updates carry no id so they never move the client's Last-Event-ID
+                        if event.updated {
+                            let frame = format!("event: updated\ndata: {}\n\n", event.payload);
+                            return Some((Ok::<Bytes, std::io::Error>(Bytes::from(frame)), rx));
+                        }
+                        // Already sent as part of the replay
+                        if entry.id <= floor {
+                            continue;
+                        }
+                        return Some((Ok::<Bytes, std::io::Error>(Bytes::from(sse_frame(entry.id, &event.payload))), rx));
+                    }
+                    // A slow subscriber is told how much it missed instead of being cut off
+                    Err(RecvError::Lagged(dropped)) => {
+                        let frame = format!("event: lag\ndata: {}\n\n", serde_json::json!({ "dropped": dropped }));
+                        return Some((Ok::<Bytes, std::io::Error>(Bytes::from(frame)), rx));
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+
+    // Comment frames keep idle proxies from closing the connection; clients ignore them
+    let period = Duration::from_secs(server_cfg.heartbeat_interval_secs.max(1));
+    let heartbeat = IntervalStream::new(tokio::time::interval_at(Instant::now() + period, period))
+        .map(|_| Some(Ok::<Bytes, std::io::Error>(Bytes::from_static(b": heartbeat\n\n"))));
+
+    // None marks the end of the live feed, which also ends the heartbeat
+    let events = futures::stream::iter(replay)
+        .chain(live)
+        .map(Some)
+        .chain(futures::stream::once(async { None }));
+    let stream = futures::stream::select(events, heartbeat)
+        .take_while(|frame| futures::future::ready(frame.is_some()))
+        .filter_map(futures::future::ready);
+
+    HttpResponse::Ok()
+        .append_header(("Content-Type", "text/event-stream"))
+        .append_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
+
+fn sse_frame(id: u64, payload: &str) -> String {
+    format!("id: {}\ndata: {}\n\n", id, payload)
+}
+
+// Metrics endpoint
+#[utoipa::path(
+    get,
+    path = "/v1/metrics",
+    responses(
+        (status = 200, description = "Prometheus text exposition", content_type = "text/plain", body = String),
+    ),
+)]
+async fn metrics(registry: web::Data<Registry>) -> HttpResponse {
+    let encoder = TextEncoder::new();
+    let metric_families = registry.gather();
+    let mut buffer = Vec::new();
+    encoder.encode(&metric_families, &mut buffer).unwrap();
+    HttpResponse::Ok()
+        .append_header(("Content-Type", encoder.format_type()))
+        .body(buffer)
+}
+
+// Retention task, removes entries older than max_age on every tick
+async fn evict_old_logs(storage: SharedStorage, max_age: TimeDelta, interval: Duration) {
+    loop {
+        sleep(interval).await;
+        if let Err(e) = storage.evict_older_than(Utc::now() - max_age).await {
+            tracing::error!(error = %e, "retention failed to evict old logs");
+        }
+    }
+}
+
+// The memory_file backend's appender, shared by the periodic task and the final flush on shutdown.
+// Lines are never rewritten, so later dedup merges and PATCHes of a flushed entry are not persisted
+struct Persister {
+    db: LogDb,
+    cfg: LoggingConfig,
+    cipher: Option<LineCipher>,
+    last_persisted_id: u64,
+    opened_on: NaiveDate,
+    ready: PersistReady,
+}
+
+type SharedPersister = Arc<tokio::sync::Mutex<Persister>>;
+
+impl Persister {
+    fn new(
+        db: LogDb,
+        cfg: LoggingConfig,
+        cipher: Option<LineCipher>,
+        last_persisted_id: u64,
+        ready: PersistReady,
+    ) -> Self {
+        Self { db, cfg, cipher, last_persisted_id, opened_on: Utc::now().date_naive(), ready }
+    }
+
+    // An entry's line in the file: its JSON, signed and then encrypted when those are configured
+    fn persisted_line(&self, entry: &LogEntry) -> Result<String, String> {
+        let json = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        let line = match &self.cfg.signing_key {
+            Some(key) => signing::sign_line(key, &json),
+            None => json,
+        };
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&line),
+            None => Ok(line),
+        }
+    }
+
+    // Append every entry stored since the last flush, rotating the file when due. Returns how many
+    // entries were written
+    async fn flush(&mut self) -> usize {
+        let logs: Vec<LogEntry> = {
+            let db_lock = self.db.read().await;
+            let mut fresh: Vec<LogEntry> = db_lock
+                .iter()
+                .rev()
+                .take_while(|log| log.id > self.last_persisted_id)
+                .cloned()
+                .collect();
+            fresh.reverse();
+            fresh
+        };
+
+        let Some(last) = logs.last() else {
+            return 0;
+        };
+        let mut lines = String::new();
+        for entry in &logs {
+            match self.persisted_line(entry) {
+                Ok(line) => {
+                    lines.push_str(&line);
+                    lines.push('\n');
+                }
+                Err(e) => tracing::error!(id = entry.id, error = %e, "persist skipping entry"),
+            }
+        }
+
+        let bytes = if self.cfg.compress_persisted {
+            // Each flush appends its own gzip member to the file
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            match gz.write_all(lines.as_bytes()).and_then(|_| gz.finish()) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    tracing::error!(error = %e, "persist failed to compress batch");
+                    return 0;
+                }
+            }
+        } else {
+            lines.into_bytes()
+        };
+
+        let path = persisted_path(&self.cfg);
+        let Some(file_size) = append_with_retry(&path, &bytes).await else {
+            // Entries stay unpersisted and are picked up again next cycle
+            return 0;
+        };
+        self.last_persisted_id = last.id;
+        self.ready.store(true, Ordering::Relaxed);
+
+        let too_big = match self.cfg.max_file_size_mb {
+            Some(max_mb) => file_size > max_mb * 1024 * 1024,
+            None => false,
+        };
+        let new_day = self.cfg.rotate_daily && Utc::now().date_naive() != self.opened_on;
+
+        if too_big || new_day {
+            match rotate_log_file(&self.cfg, self.cfg.max_rotated_files).await {
+                Ok(()) => self.opened_on = Utc::now().date_naive(),
+                Err(e) => tracing::error!(path, error = %e, "persist failed to rotate"),
+            }
+        }
+
+        logs.len()
+    }
+
+    // After a full DELETE /logs: empty the files startup loads from, so cleared entries do not
+    // come back on restart, and start over from id 1 to match the reset sequence
+    async fn reset(&mut self) -> std::io::Result<()> {
+        let active = persisted_path(&self.cfg);
+        tokio::fs::write(&active, b"").await?;
+        if active != self.cfg.file_path && tokio::fs::try_exists(&self.cfg.file_path).await? {
+            tokio::fs::write(&self.cfg.file_path, b"").await?;
+        }
+        self.last_persisted_id = 0;
+        Ok(())
+    }
+}
+
+async fn persist_logs(persister: SharedPersister) {
+    let (path, interval) = {
+        let persister = persister.lock().await;
+        (persisted_path(&persister.cfg), Duration::from_secs(persister.cfg.persist_interval_secs))
+    };
+
+    // Confirm the file is writable up front so readiness does not wait for the first log
+    if append_with_retry(&path, &[]).await.is_some() {
+        persister.lock().await.ready.store(true, Ordering::Relaxed);
+    }
+
+    loop {
+        sleep(interval).await;
+        persister.lock().await.flush().await;
+    }
+}
+
+// Append bytes to a file, returning its new size
+async fn append_to_file(path: &str, bytes: &[u8]) -> std::io::Result<u64> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(bytes).await?;
+    file.flush().await?;
+    Ok(file.metadata().await?.len())
+}
+
+// Retry a flush with exponential backoff, giving up after PERSIST_ATTEMPTS
+async fn append_with_retry(path: &str, bytes: &[u8]) -> Option<u64> {
+    let mut delay = PERSIST_RETRY_BASE;
+    for attempt in 1..=PERSIST_ATTEMPTS {
+        match append_to_file(path, bytes).await {
+            Ok(size) => return Some(size),
+            Err(e) => {
+                tracing::warn!(path, attempt, max_attempts = PERSIST_ATTEMPTS, error = %e, "persist write failed");
+                if attempt < PERSIST_ATTEMPTS {
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+    tracing::error!(path, "persist giving up on this flush cycle");
+    None
+}
+
+// File currently being appended to, compressed output always carries a .gz extension
+fn persisted_path(cfg: &LoggingConfig) -> String {
+    if cfg.compress_persisted && !cfg.file_path.ends_with(".gz") {
+        format!("{}.gz", cfg.file_path)
+    } else {
+        cfg.file_path.clone()
+    }
+}
+
+// Move the current log file aside as <file_path>.<timestamp>.log[.gz] and prune old archives
+async fn rotate_log_file(cfg: &LoggingConfig, max_rotated_files: usize) -> std::io::Result<()> {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%S%3f");
+    let ext = if cfg.compress_persisted { "log.gz" } else { "log" };
+    tokio::fs::rename(persisted_path(cfg), format!("{}.{}.{}", cfg.file_path, stamp, ext)).await?;
+
+    let path = Path::new(&cfg.file_path);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+
+    let mut archives = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && (name.ends_with(".log") || name.ends_with(".log.gz")) {
+            archives.push(entry.path());
+        }
+    }
+    // Timestamps sort lexically, so the oldest archives come first
+    archives.sort();
+
+    let excess = archives.len().saturating_sub(max_rotated_files);
+    for old in &archives[..excess] {
+        tokio::fs::remove_file(old).await?;
+    }
+
+    Ok(())
+}
+
+// The persisted file's text, decompressed if it is gzipped; empty if it does not exist yet
+async fn read_persisted_file(path: &str) -> std::io::Result<String> {
+    let raw = match tokio::fs::read(path).await {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(String::new()),
+        Err(e) => return Err(e),
+    };
+
+    // MultiGzDecoder reads every appended gzip member, not just the first
+    if path.ends_with(".gz") {
+        let mut decoded = String::new();
+        MultiGzDecoder::new(raw.as_slice()).read_to_string(&mut decoded)?;
+        Ok(decoded)
+    } else {
+        Ok(String::from_utf8_lossy(&raw).into_owned())
+    }
+}
+
+// The cipher for [logging] encryption_key_hex, if one is set
+fn line_cipher(cfg: &LoggingConfig) -> Result<Option<LineCipher>, String> {
+    cfg.encryption_key_hex.as_deref().map(LineCipher::from_hex).transpose()
+}
+
+// Everything the memory_file backend starts with: the newest max_memory_logs persisted entries
+async fn load_initial_logs(cfg: &LoggingConfig, cipher: Option<&LineCipher>) -> std::io::Result<VecDeque<LogEntry>> {
+    // A plain file written before compression was enabled is still loaded, ahead of the .gz one
+    let active_path = persisted_path(cfg);
+    let mut sources = vec![cfg.file_path.clone()];
+    if active_path != cfg.file_path {
+        sources.push(active_path);
+    }
+
+    let mut initial_logs = VecDeque::new();
+    for source in &sources {
+        initial_logs.extend(load_logs_from_file(source, cipher).await?);
+    }
+    evict_overflow(&mut initial_logs, cfg.max_memory_logs);
+
+    // Ids are not persisted across restarts, so loaded entries are renumbered from 1
+    for (i, entry) in initial_logs.iter_mut().enumerate() {
+        entry.id = i as u64 + 1;
+    }
+    Ok(initial_logs)
+}
+
+// Read previously persisted entries, accepting either a single JSON array or NDJSON.
+// A missing file counts as empty and unparseable entries are skipped with a warning.
+async fn load_logs_from_file(path: &str, cipher: Option<&LineCipher>) -> std::io::Result<Vec<LogEntry>> {
+    let content = read_persisted_file(path).await?;
+
+    let mut logs = Vec::new();
+    let mut rest = content.as_str();
+
+    // A legacy array file may have NDJSON appended after it by later runs
+    if rest.trim_start().starts_with('[') {
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Vec<serde_json::Value>>();
+        match stream.next() {
+            Some(Ok(values)) => {
+                for (i, value) in values.into_iter().enumerate() {
+                    match serde_json::from_value::<LogEntry>(value) {
+                        Ok(entry) => logs.push(entry),
+                        Err(e) => tracing::warn!(index = i, path, error = %e, "skipping undecodable persisted entry"),
+                    }
+                }
+                rest = &rest[stream.byte_offset()..];
+            }
+            Some(Err(e)) => {
+                tracing::warn!(path, error = %e, "could not parse persisted file as a JSON array");
+                rest = "";
+            }
+            None => {}
+        }
+    }
+
+    // One bad line is skipped, but a key that opens none of them is almost certainly the wrong key
+    let (mut decrypted, mut undecryptable) = (0, 0);
+    for (i, line) in rest.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let encrypted = cipher.is_some() && !encryption::is_plain(line);
+        let line = match encryption::decode_line(cipher, line) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!(line = i + 1, path, error = %e, "skipping undecryptable persisted line");
+                undecryptable += 1;
+                continue;
+            }
+        };
+        if encrypted {
+            decrypted += 1;
+        }
+        match serde_json::from_str::<LogEntry>(&line) {
+            Ok(entry) => logs.push(entry),
+            Err(e) => tracing::warn!(line = i + 1, path, error = %e, "skipping undecodable persisted line"),
+        }
+    }
+    if undecryptable > 0 && decrypted == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("none of the {} encrypted lines in {} decrypt, check encryption_key_hex", undecryptable, path),
+        ));
+    }
+
+    Ok(logs)
+}
+
+// Pause between attempts to reach a database backend at startup
+const STARTUP_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+// Connect to a database backend and check it answers, retrying until timeout, so a server that
+// would fail every request never binds its port
+async fn connect_backend<S, F, Fut>(name: &str, timeout: Duration, mut connect: F) -> std::io::Result<S>
+where
+    S: Storage,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<S, String>>,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let attempt = async {
+            let storage = connect().await?;
+            storage.health_check().await.map_err(|e| format!("{} health check failed: {}", name, e))?;
+            Ok::<S, String>(storage)
+        };
+        // A single connect can otherwise hang for the driver's own, much longer, timeout
+        let remaining = deadline.saturating_duration_since(Instant::now()).max(STARTUP_RETRY_INTERVAL);
+        let error = match tokio::time::timeout(remaining, attempt).await {
+            Ok(Ok(storage)) => return Ok(storage),
+            Ok(Err(e)) => e,
+            Err(_) => format!("{} did not answer in time", name),
+        };
+        if Instant::now() + STARTUP_RETRY_INTERVAL > deadline {
+            return Err(std::io::Error::other(format!(
+                "{} unavailable after {}s, not starting: {}",
+                name,
+                timeout.as_secs(),
+                error
+            )));
+        }
+        tracing::warn!(backend = name, error = %error, "storage backend not ready, retrying");
+        sleep(STARTUP_RETRY_INTERVAL).await;
+    }
+}
+
+// Every versioned route, with the write endpoints behind X-API-Key or a signature
+fn api_scope(
+    server_cfg: &ServerConfig,
+    namespaces: &Namespaces,
+    seen_signatures: &SeenSignatures,
+    signed_body_limit: usize,
+) -> actix_web::Scope {
+    // Exactly one of these checks the write endpoints, depending on hmac_auth
+    let api_key = || {
+        Condition::new(!server_cfg.hmac_auth, ApiKeyMiddleware::new(server_cfg.api_key.as_deref(), namespaces.clone()))
+    };
+    let hmac_auth = || {
+        Condition::new(
+            server_cfg.hmac_auth,
+            HmacAuthMiddleware::new(
+                server_cfg.api_key.as_deref(),
+                namespaces,
+                seen_signatures.clone(),
+                signed_body_limit,
+            ),
+        )
+    };
+
+    web::scope(API_PREFIX)
+        .route("/health", web::get().to(health))
+        .route("/ready", web::get().to(ready))
+        .service(
+            web::resource("/logs")
+                .app_data(post_log_json_config(server_cfg.max_body_bytes))
+                .route(web::post().to(post_log).wrap(api_key()).wrap(hmac_auth()))
+                .route(web::get().to(get_logs))
+                .route(web::delete().to(clear_logs).wrap(api_key()).wrap(hmac_auth())),
+        )
+        .route("/logs/batch", web::post().to(post_logs_batch).wrap(api_key()).wrap(hmac_auth()))
+        .route("/logs/import", web::post().to(import_logs).wrap(api_key()).wrap(hmac_auth()))
+        .route("/logs/purge", web::post().to(purge_logs).wrap(api_key()).wrap(hmac_auth()))
+        .route("/logs/verify", web::post().to(verify_logs).wrap(api_key()).wrap(hmac_auth()))
+        .route("/logs/stats", web::get().to(get_stats))
+        .route("/logs/diff", web::get().to(get_logs_diff))
+        .route("/logs/tail", web::get().to(get_logs_tail))
+        .route("/logs/count", web::get().to(get_log_count))
+        .route("/logs/export", web::get().to(export_logs))
+        .route("/logs/trace/{trace_id}", web::get().to(get_logs_by_trace))
+        .route("/logs/similar/{id}", web::get().to(get_similar_logs))
+        .route("/logs/service_graph", web::get().to(get_service_graph))
+        .route("/logs/services", web::get().to(get_services))
+        .route("/logs/levels", web::get().to(get_levels))
+        .route("/logs/fields", web::get().to(get_fields))
+        .route("/logs/aggregate", web::get().to(get_logs_aggregate))
+        .route("/logs/top_errors", web::get().to(get_top_errors))
+        .route("/logs/error_rate", web::get().to(get_error_rate))
+        .route("/logs/service_health", web::get().to(get_service_health))
+        .route("/logs/stream", web::get().to(logs_stream))
+        .route("/logs/ws", web::get().to(ws::logs_ws))
+        .route("/logs/{id}", web::get().to(get_log_by_id))
+        .route("/logs/{id}", web::delete().to(delete_log).wrap(api_key()).wrap(hmac_auth()))
+        .route("/logs/{id}", web::patch().to(patch_log).wrap(api_key()).wrap(hmac_auth()))
+        .route("/metrics", web::get().to(metrics))
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+    let cfg = load_config();
+
+    // Fail fast on a bad pattern rather than storing unredacted entries
+    let pii = match &cfg.pii_scrubbing {
+        Some(pii_cfg) => PiiScrubber::new(pii_cfg)
+            .map_err(|e| std::io::Error::other(format!("invalid pii_scrubbing pattern: {}", e)))?,
+        None => PiiScrubber::default(),
+    };
+    let pii = web::Data::new(pii);
+    let dedup = web::Data::new(Deduplicator::new(cfg.logging.enable_deduplication, cfg.logging.max_dedup_entries));
+
+    let tracer_provider = telemetry::init_tracing(cfg.observability.as_ref())?;
+
+    // Prometheus metrics
+    let registry = Registry::new();
+    let total_logs = IntCounter::new("total_logs", "Total number of logs received").unwrap();
+    registry.register(Box::new(total_logs.clone())).unwrap();
+    let request_duration = HistogramVec::new(
+        HistogramOpts::new("http_request_duration_seconds", "HTTP request latency in seconds"),
+        &["method", "route", "status"],
+    )
+    .unwrap();
+    registry.register(Box::new(request_duration.clone())).unwrap();
+    let logs_ingested = CounterVec::new(
+        Opts::new("logs_ingested_total", "Logs accepted on ingest, by service and level"),
+        &["service", "level"],
+    )
+    .unwrap();
+    registry.register(Box::new(logs_ingested.clone())).unwrap();
+    let logs_in_memory = Gauge::new("logs_in_memory", "Entries currently held in the in-memory store").unwrap();
+    registry.register(Box::new(logs_in_memory.clone())).unwrap();
+    let webhook_failures = IntCounterVec::new(
+        Opts::new("webhook_failures_total", "Alert webhook deliveries that failed after every retry"),
+        &["alert"],
+    )
+    .unwrap();
+    registry.register(Box::new(webhook_failures.clone())).unwrap();
+    let top_error_count = IntGauge::new("top_error_count", "Occurrences of the most frequent ERROR message").unwrap();
+    registry.register(Box::new(top_error_count.clone())).unwrap();
+    let syslog_parse_errors =
+        IntCounter::new("syslog_parse_errors_total", "Syslog datagrams discarded as malformed").unwrap();
+    registry.register(Box::new(syslog_parse_errors.clone())).unwrap();
+    let clock_skew_events =
+        IntCounter::new("clock_skew_events_total", "Ingested entries flagged for clock skew").unwrap();
+    registry.register(Box::new(clock_skew_events.clone())).unwrap();
+    let oversized_messages =
+        IntCounter::new("oversized_messages_total", "Entries whose message exceeded max_message_bytes").unwrap();
+    registry.register(Box::new(oversized_messages.clone())).unwrap();
+    let rejected_unknown_services = IntCounter::new(
+        "rejected_unknown_service_total",
+        "Entries rejected because their service was new and max_services was reached",
+    )
+    .unwrap();
+    registry.register(Box::new(rejected_unknown_services.clone())).unwrap();
+    let filtered_below_min_level = IntCounterVec::new(
+        Opts::new("filtered_below_min_level_total", "Entries dropped by [level_filters], by service and level"),
+        &["service", "level"],
+    )
+    .unwrap();
+    registry.register(Box::new(filtered_below_min_level.clone())).unwrap();
+    let level_filters = LevelFilters::new(&cfg.level_filters, filtered_below_min_level).map_err(std::io::Error::other)?;
+    let level_filters = web::Data::new(level_filters);
+
+    let bcast: Broadcaster = Arc::new(broadcast::channel(cfg.server.broadcast_capacity.max(1)).0);
+    let cipher = line_cipher(&cfg.logging).map_err(std::io::Error::other)?;
+    let persist_ready: PersistReady = Arc::new(AtomicBool::new(false));
+
+    let startup_timeout = Duration::from_secs(args.startup_timeout_secs);
+    // Only the memory_file backend has anything left to write at shutdown
+    let mut persister: Option<SharedPersister> = None;
+    let (storage, loaded): (SharedStorage, u64) = match cfg.logging.storage_backend.as_str() {
+        "memory_file" => {
+            let initial_logs = if cfg.logging.overwrite_on_start {
+                tokio::fs::File::create(persisted_path(&cfg.logging)).await?;
+                VecDeque::new()
+            } else {
+                load_initial_logs(&cfg.logging, cipher.as_ref()).await?
+            };
+            let loaded = initial_logs.len() as u64;
+            let db: LogDb = Arc::new(RwLock::new(initial_logs));
+
+            // Spawn persistence task
+            let shared = Arc::new(tokio::sync::Mutex::new(Persister::new(
+                db.clone(),
+                cfg.logging.clone(),
+                cipher,
+                loaded,
+                persist_ready.clone(),
+            )));
+            tokio::spawn(persist_logs(shared.clone()));
+            persister = Some(shared);
+
+            if let Some(backup_cfg) = cfg.s3_backup.clone() {
+                tokio::spawn(backup::run_s3_backup(backup_cfg, persisted_path(&cfg.logging)));
+            }
+
+            logs_in_memory.set(loaded as f64);
+            (Arc::new(MemoryStorage::new(db, cfg.logging.max_memory_logs, logs_in_memory.clone())), loaded)
+        }
+        "sqlite" => {
+            if cfg.s3_backup.is_some() {
+                tracing::warn!("s3_backup only covers the memory_file backend, ignoring it");
+            }
+            let sqlite = connect_backend("sqlite", startup_timeout, || async {
+                SqliteStorage::connect(&cfg.logging.sqlite_url)
+                    .await
+                    .map_err(|e| format!("failed to open {}: {}", cfg.logging.sqlite_url, e))
+            })
+            .await?;
+            // Every insert is durable on its own, so there is nothing left to confirm
+            persist_ready.store(true, Ordering::Relaxed);
+            let max_id = sqlite.max_id().await.map_err(std::io::Error::other)?;
+            (Arc::new(sqlite), max_id)
+        }
+        "redis" => {
+            let Some(redis_cfg) = &cfg.redis else {
+                return Err(std::io::Error::other("storage_backend \"redis\" needs a [redis] section"));
+            };
+            if cfg.s3_backup.is_some() {
+                tracing::warn!("s3_backup only covers the memory_file backend, ignoring it");
+            }
+            let redis = connect_backend("redis", startup_timeout, || async {
+                RedisStorage::connect(redis_cfg, cfg.logging.max_memory_logs)
+                    .await
+                    .map_err(|e| format!("failed to connect to {}: {}", redis_cfg.url, e))
+            })
+            .await?;
+            persist_ready.store(true, Ordering::Relaxed);
+            let max_id = redis.max_id().await.map_err(std::io::Error::other)?;
+            (Arc::new(redis), max_id)
+        }
+        "postgres" => {
+            let Some(postgres_cfg) = &cfg.postgres else {
+                return Err(std::io::Error::other("storage_backend \"postgres\" needs a [postgres] section"));
+            };
+            if cfg.s3_backup.is_some() {
+                tracing::warn!("s3_backup only covers the memory_file backend, ignoring it");
+            }
+            let postgres = connect_backend("postgres", startup_timeout, || async {
+                PostgresStorage::connect(postgres_cfg).await.map_err(|e| format!("failed to connect to postgres: {}", e))
+            })
+            .await?;
+            persist_ready.store(true, Ordering::Relaxed);
+            let max_id = postgres.max_id().await.map_err(std::io::Error::other)?;
+            (Arc::new(postgres), max_id)
+        }
+        other => {
+            return Err(std::io::Error::other(format!(
+                "unknown storage_backend {:?}, expected \"memory_file\", \"sqlite\", \"redis\" or \"postgres\"",
+                other
+            )));
+        }
+    };
+    let seq: LogSequence = Arc::new(AtomicU64::new(loaded));
+    let service_limit = web::Data::new(
+        ServiceLimit::new(cfg.logging.max_services, &storage, rejected_unknown_services)
+            .await
+            .map_err(std::io::Error::other)?,
+    );
+
+    if let Some(max_age_secs) = cfg.logging.max_age_secs {
+        let interval = Duration::from_secs(cfg.logging.evict_interval_secs.max(1));
+        tokio::spawn(evict_old_logs(storage.clone(), TimeDelta::seconds(max_age_secs as i64), interval));
+    }
+
+    if !cfg.alerts.is_empty() {
+        tokio::spawn(alerts::alert_monitor(storage.clone(), cfg.alerts.clone(), webhook_failures.clone()));
+    }
+
+    let forwarder = match &cfg.kafka_producer {
+        #[cfg(feature = "kafka")]
+        Some(producer_cfg) => {
+            if cfg.kafka.as_ref().is_some_and(|k| k.topic == producer_cfg.topic) {
+                tracing::warn!("kafka_producer.topic is also consumed, every entry will loop back in");
+            }
+            let errors = IntCounter::new("kafka_produce_errors_total", "Entries that could not be forwarded to Kafka").unwrap();
+            registry.register(Box::new(errors.clone())).unwrap();
+            KafkaForwarder::new(producer_cfg, errors)
+                .map_err(|e| std::io::Error::other(format!("invalid kafka_producer config: {}", e)))?
+        }
+        #[cfg(not(feature = "kafka"))]
+        Some(producer_cfg) => {
+            tracing::warn!(topic = %producer_cfg.topic, "built without the kafka feature, ignoring [kafka_producer]");
+            KafkaForwarder::default()
+        }
+        None => KafkaForwarder::default(),
+    };
+    let rooms = Rooms::new(cfg.server.broadcast_capacity);
+    tokio::spawn(rooms.clone().sweep_idle(Duration::from_secs(cfg.server.room_ttl_secs)));
+    let sinks = Sinks::new(vec![
+        Box::new(MetricsSink { total_logs: total_logs.clone(), logs_ingested: logs_ingested.clone() }),
+        Box::new(BroadcastSink(bcast.clone())),
+        Box::new(rooms.clone()),
+        Box::new(forwarder),
+    ]);
+
+    let message_limit =
+        web::Data::new(MessageLimit { max_bytes: cfg.logging.max_message_bytes, oversized: oversized_messages });
+    let clock_skew = web::Data::new(ClockSkewCheck {
+        max_skew: TimeDelta::seconds(cfg.logging.max_clock_skew_secs as i64),
+        events: clock_skew_events,
+    });
+    let ingest_ctx = IngestContext {
+        storage: storage.clone(),
+        seq: seq.clone(),
+        pii: pii.clone(),
+        dedup: dedup.clone(),
+        sinks: sinks.clone(),
+        message_limit: message_limit.clone(),
+        service_limit: service_limit.clone(),
+        level_filters: level_filters.clone(),
+        skew: clock_skew.clone(),
+    };
+
+    let (bus, bus_rx): (LogBus, _) = tokio::sync::mpsc::channel(cfg.logging.bus_capacity.max(1));
+    let dispatcher = tokio::spawn(bus::dispatcher(bus_rx, ingest_ctx.clone()));
+    if let Some(ingest_cfg) = &cfg.ingest
+        && let Some(port) = ingest_cfg.tcp_port
+    {
+        // Bind before serving HTTP so a taken port fails startup
+        let listener = tokio::net::TcpListener::bind((cfg.server.host.as_str(), port)).await?;
+        tracing::info!(host = %cfg.server.host, port, "tcp ingestion listening");
+        tokio::spawn(ingest::tcp_ingestion_task(listener, ingest_cfg.tcp_max_connections, ingest_ctx.clone()));
+    }
+    if let Some(ingest_cfg) = &cfg.ingest
+        && let Some(port) = ingest_cfg.udp_syslog_port
+    {
+        let socket = tokio::net::UdpSocket::bind((cfg.server.host.as_str(), port)).await?;
+        tracing::info!(host = %cfg.server.host, port, "udp syslog listening");
+        tokio::spawn(syslog::udp_syslog_task(socket, ingest_ctx.clone(), syslog_parse_errors.clone()));
+    }
+
+    if let Some(kafka_cfg) = &cfg.kafka {
+        #[cfg(feature = "kafka")]
+        {
+            let consumed = IntCounter::new("kafka_messages_consumed_total", "Kafka messages stored as log entries").unwrap();
+            registry.register(Box::new(consumed.clone())).unwrap();
+            let errors = IntCounter::new("kafka_consumer_errors_total", "Kafka receive, decode and commit failures").unwrap();
+            registry.register(Box::new(errors.clone())).unwrap();
+
+            let task = kafka::KafkaConsumerTask { cfg: kafka_cfg.clone(), ctx: ingest_ctx.clone(), consumed, errors };
+            tokio::spawn(task.run());
+        }
+        #[cfg(not(feature = "kafka"))]
+        tracing::warn!(topic = %kafka_cfg.topic, "built without the kafka feature, ignoring [kafka]");
+    }
+
+    let logging_cfg = cfg.logging.clone();
+    let max_body_bytes = cfg.server.max_body_bytes;
+    // The largest body any signed endpoint takes; each handler still applies its own limit after
+    let signed_body_limit = max_body_bytes.max(cfg.logging.import_max_mb.saturating_mul(1024 * 1024) as usize);
+    let server_cfg = cfg.server.clone();
+    let app_cfg = cfg.clone();
+
+    let rate_buckets: RateLimitBuckets = Arc::default();
+    let namespaces = Namespaces::new(cfg.server.namespaces.clone());
+    let field_mapping = web::Data::new(cfg.field_mapping.clone());
+    let query_cache = web::Data::new(QueryCache::new(Duration::from_millis(cfg.logging.query_cache_ttl_ms)));
+    // Built once; SwaggerUi serves it at /openapi.json alongside its assets
+    let api_doc = ApiDoc::openapi();
+    if cfg.rate_limit.is_some() {
+        tokio::spawn(rate_limit::sweep_idle_buckets(rate_buckets.clone()));
+    }
+
+    // Load TLS material before binding so a bad certificate fails startup
+    let tls_config = cfg.tls.as_ref().map(load_tls_config).transpose()?;
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+    if let Some(cors_cfg) = &cfg.cors {
+        cors_cfg.validate().map_err(std::io::Error::other)?;
+    }
+    if let Some(level) = cfg.server.compress_level
+        && level > 9
+    {
+        return Err(std::io::Error::other(format!("compress_level must be between 0 and 9, got {}", level)));
+    }
+    if cfg.server.hmac_auth && cfg.server.api_key.is_none() && cfg.server.namespaces.is_empty() {
+        return Err(std::io::Error::other("hmac_auth needs api_key or [server.namespaces] keys to sign with"));
+    }
+    let seen_signatures = SeenSignatures::default();
+    // DELETE /logs empties the file through it; the original is kept for the flush at shutdown
+    let app_persister = persister.clone();
+
+    tracing::info!(host = %cfg.server.host, port = cfg.server.port, scheme, "server started");
+
+    let server = HttpServer::new(move || {
+        let cors = app_cfg.cors.as_ref();
+
+        App::new()
+            .wrap(Condition::new(cors.is_some(), cors.map_or_else(Cors::default, CorsConfig::middleware)))
+            .wrap(CorsPreflightMiddleware::new(cors))
+            // Outside every middleware that answers on its own, so 401s and 403s are compressed too
+            .wrap(GzipLevelMiddleware::new(server_cfg.compress_level))
+            .wrap(Compress::default())
+            .wrap(PromMiddleware::new(request_duration.clone()))
+            .wrap(TracingLogger::default())
+            .wrap(RequestIdMiddleware)
+            .app_data(web::Data::new(storage.clone()))
+            .app_data(web::Data::new(bcast.clone()))
+            .app_data(web::Data::new(rooms.clone()))
+            .app_data(web::Data::new(top_error_count.clone()))
+            .app_data(web::Data::new(registry.clone()))
+            .app_data(web::Data::new(logging_cfg.clone()))
+            .app_data(web::Data::new(server_cfg.clone()))
+            .app_data(web::Data::new(seq.clone()))
+            .app_data(web::Data::new(persist_ready.clone()))
+            .app_data(web::Data::new(app_persister.clone()))
+            .app_data(web::Data::new(app_cfg.clone()))
+            .app_data(web::Data::new(rate_buckets.clone()))
+            .app_data(query_cache.clone())
+            .app_data(pii.clone())
+            .app_data(dedup.clone())
+            .app_data(web::Data::new(sinks.clone()))
+            .app_data(field_mapping.clone())
+            .app_data(clock_skew.clone())
+            .app_data(message_limit.clone())
+            .app_data(service_limit.clone())
+            .app_data(level_filters.clone())
+            .app_data(web::Data::new(bus.clone()))
+            .app_data(web::Data::new(namespaces.clone()))
+            .route("/", web::get().to(index))
+            .service(api_scope(&server_cfg, &namespaces, &seen_signatures, signed_body_limit))
+            // The unversioned paths predate /v1 and stay around as redirects
+            .route("/health", web::to(redirect_to_versioned))
+            .route("/ready", web::to(redirect_to_versioned))
+            .route("/metrics", web::to(redirect_to_versioned))
+            .route("/logs", web::to(redirect_to_versioned))
+            .route("/logs/{tail:.*}", web::to(redirect_to_versioned))
+            .service(SwaggerUi::new("/swagger-ui/{_:.*}").url("/openapi.json", api_doc.clone()))
+    });
+
+    let server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23((cfg.server.host, cfg.server.port), tls_config)?,
+        None => server.bind((cfg.server.host, cfg.server.port))?,
+    };
+
+    // Signals are handled here rather than by actix so the store can be flushed afterwards
+    let server = server.disable_signals().shutdown_timeout(SHUTDOWN_GRACE.as_secs()).run();
+    tokio::spawn(stop_on_signal(server.handle()));
+    let result = server.await;
+
+    // Every copy of the bus went with the server, so the dispatcher stores whatever was still
+    // queued and returns
+    if tokio::time::timeout(SHUTDOWN_GRACE, dispatcher).await.is_err() {
+        tracing::warn!("ingest queue not drained before the shutdown deadline");
+    }
+    let flushed_entries = match &persister {
+        Some(persister) if args.flush_on_exit => persister.lock().await.flush().await,
+        _ => 0,
+    };
+    tracing::info!(event = "shutdown", flushed_entries, "shutdown");
+
+    if let Some(provider) = tracer_provider {
+        telemetry::shutdown_tracer(provider).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::MessageBody;
+    use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+
+    // An AppConfig as config.toml would give it, with these lines added under [server] and [logging]
+    pub(crate) fn test_config(server: &str, logging: &str) -> AppConfig {
+        let toml = format!(
+            "[server]\nhost = \"127.0.0.1\"\nport = 0\n{}\n[logging]\nfile_path = \"\"\nmax_memory_logs = 100\npersist_interval_secs = 5\n{}\n",
+            server, logging
+        );
+        Config::builder()
+            .add_source(config::File::from_str(&toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    pub(crate) fn memory_storage(cfg: &AppConfig) -> SharedStorage {
+        Arc::new(MemoryStorage::new(
+            Arc::default(),
+            cfg.logging.max_memory_logs,
+            Gauge::new("logs_in_memory", "logs_in_memory").unwrap(),
+        ))
+    }
+
+    // The versioned API as main serves it, over the given store and without a persister
+    pub(crate) async fn test_app(
+        cfg: AppConfig,
+        storage: SharedStorage,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        let registry = Registry::new();
+        let total_logs = IntCounter::new("total_logs", "Total number of logs received").unwrap();
+        registry.register(Box::new(total_logs.clone())).unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency in seconds"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        let logs_ingested = CounterVec::new(Opts::new("logs_ingested_total", "Logs accepted on ingest"), &["service", "level"]).unwrap();
+        let counter = |name: &str| IntCounter::new(name, name).unwrap();
+
+        let seq: LogSequence = Arc::default();
+        let bcast: Broadcaster = Arc::new(broadcast::channel(cfg.server.broadcast_capacity.max(1)).0);
+        let rooms = Rooms::new(cfg.server.broadcast_capacity);
+        let sinks = Sinks::new(vec![
+            Box::new(MetricsSink { total_logs, logs_ingested }),
+            Box::new(BroadcastSink(bcast.clone())),
+            Box::new(rooms.clone()),
+            Box::new(KafkaForwarder::default()),
+        ]);
+        let pii = web::Data::new(cfg.pii_scrubbing.as_ref().map_or_else(PiiScrubber::default, |p| PiiScrubber::new(p).unwrap()));
+        let dedup = web::Data::new(Deduplicator::new(cfg.logging.enable_deduplication, cfg.logging.max_dedup_entries));
+        let message_limit =
+            web::Data::new(MessageLimit { max_bytes: cfg.logging.max_message_bytes, oversized: counter("oversized") });
+        let service_limit =
+            web::Data::new(ServiceLimit::new(cfg.logging.max_services, &storage, counter("rejected")).await.unwrap());
+        let level_filters = web::Data::new(
+            LevelFilters::new(&cfg.level_filters, IntCounterVec::new(Opts::new("filtered", "filtered"), &["service", "level"]).unwrap())
+                .unwrap(),
+        );
+        let clock_skew = web::Data::new(ClockSkewCheck {
+            max_skew: TimeDelta::seconds(cfg.logging.max_clock_skew_secs as i64),
+            events: counter("skew"),
+        });
+        let ingest_ctx = IngestContext {
+            storage: storage.clone(),
+            seq: seq.clone(),
+            pii: pii.clone(),
+            dedup: dedup.clone(),
+            sinks: sinks.clone(),
+            message_limit: message_limit.clone(),
+            service_limit: service_limit.clone(),
+            level_filters: level_filters.clone(),
+            skew: clock_skew.clone(),
+        };
+        let (bus, bus_rx): (LogBus, _) = tokio::sync::mpsc::channel(cfg.logging.bus_capacity.max(1));
+        tokio::spawn(bus::dispatcher(bus_rx, ingest_ctx));
+
+        let namespaces = Namespaces::new(cfg.server.namespaces.clone());
+        let persister: Option<SharedPersister> = None;
+        let scope = api_scope(&cfg.server, &namespaces, &SeenSignatures::default(), cfg.server.max_body_bytes);
+
+        App::new()
+            .wrap(PromMiddleware::new(request_duration))
+            .wrap(RequestIdMiddleware)
+            .app_data(web::Data::new(storage))
+            .app_data(web::Data::new(bcast))
+            .app_data(web::Data::new(rooms))
+            .app_data(web::Data::new(IntGauge::new("top_error_count", "top_error_count").unwrap()))
+            .app_data(web::Data::new(registry))
+            .app_data(web::Data::new(cfg.logging.clone()))
+            .app_data(web::Data::new(cfg.server.clone()))
+            .app_data(web::Data::new(seq))
+            .app_data(web::Data::new(PersistReady::new(AtomicBool::new(true))))
+            .app_data(web::Data::new(persister))
+            .app_data(web::Data::new(RateLimitBuckets::default()))
+            .app_data(web::Data::new(QueryCache::new(Duration::from_millis(cfg.logging.query_cache_ttl_ms))))
+            .app_data(pii)
+            .app_data(dedup)
+            .app_data(web::Data::new(sinks))
+            .app_data(web::Data::new(cfg.field_mapping.clone()))
+            .app_data(clock_skew)
+            .app_data(message_limit)
+            .app_data(service_limit)
+            .app_data(level_filters)
+            .app_data(web::Data::new(bus))
+            .app_data(web::Data::new(namespaces))
+            .app_data(web::Data::new(cfg))
+            .service(scope)
+    }
+
+    pub(crate) fn entry_json(service: &str, level: &str, message: &str) -> serde_json::Value {
+        serde_json::json!({ "timestamp": Utc::now().to_rfc3339(), "service": service, "level": level, "message": message })
+    }
+
+    // POST /logs answers before the dispatcher stores the entry; wait until n entries are in
+    pub(crate) async fn wait_for_logs(storage: &SharedStorage, n: usize) -> Vec<LogEntry> {
+        for _ in 0..100 {
+            let logs = storage.query(&FilterParams::default()).await.unwrap();
+            if logs.len() >= n {
+                return logs;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        panic!("fewer than {} entries stored in time", n);
+    }
+
+    #[actix_web::test]
+    async fn write_routes_require_api_key() {
+        let cfg = test_config("api_key = \"secret\"", "");
+        let storage = memory_storage(&cfg);
+        let app = test::init_service(test_app(cfg, storage.clone()).await).await;
+
+        let post = || test::TestRequest::post().uri("/v1/logs").set_json(entry_json("auth", "INFO", "hello"));
+        let resp = test::call_service(&app, post().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = test::call_service(&app, post().insert_header(("X-API-Key", "wrong")).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = test::call_service(&app, post().insert_header(("X-API-Key", "secret")).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let id = wait_for_logs(&storage, 1).await[0].id;
+
+        let delete = || test::TestRequest::delete().uri(&format!("/v1/logs/{}", id));
+        let resp = test::call_service(&app, delete().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(storage.get(id).await.unwrap().is_some());
+        let resp = test::call_service(&app, delete().insert_header(("X-API-Key", "secret")).to_request()).await;
+        assert!(resp.status().is_success());
+
+        // Reads stay open
+        let resp = test::call_service(&app, test::TestRequest::get().uri("/v1/logs").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn deleted_entry_is_gone() {
+        let cfg = test_config("", "");
+        let storage = memory_storage(&cfg);
+        let app = test::init_service(test_app(cfg, storage.clone()).await).await;
+
+        for message in ["keep", "drop"] {
+            let req = test::TestRequest::post().uri("/v1/logs").set_json(entry_json("orders", "INFO", message));
+            assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::ACCEPTED);
+        }
+        let logs = wait_for_logs(&storage, 2).await;
+        let id = logs.iter().find(|e| e.message == "drop").unwrap().id;
+
+        let delete = || test::TestRequest::delete().uri(&format!("/v1/logs/{}", id)).to_request();
+        assert_eq!(test::call_service(&app, delete()).await.status(), StatusCode::NO_CONTENT);
+        let req = test::TestRequest::get().uri(&format!("/v1/logs/{}", id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+        assert_eq!(test::call_service(&app, delete()).await.status(), StatusCode::NOT_FOUND);
+
+        let rest = storage.query(&FilterParams::default()).await.unwrap();
+        assert_eq!(rest.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), ["keep"]);
+    }
+
+    #[actix_web::test]
+    async fn serves_entries_loaded_from_file() {
+        let dir = std::env::temp_dir().join(format!("log_aggregator_load_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logs.json");
+        // A legacy array with NDJSON appended by a later run, one line of it unparseable
+        let fixture = [
+            r#"[{"timestamp":"2026-01-01T00:00:00Z","service":"a","level":"INFO","message":"one"},"#,
+            r#"{"timestamp":"2026-01-01T00:00:01Z","service":"a","level":"WARN","message":"two"}]"#,
+            r#"{"timestamp":"2026-01-01T00:00:02Z","service":"b","level":"ERROR","message":"three"}"#,
+            "not json",
+            r#"{"timestamp":"2026-01-01T00:00:03Z","service":"b","level":"INFO","message":"four"}"#,
+        ]
+        .join("\n");
+        std::fs::write(&path, fixture).unwrap();
+
+        let mut cfg = test_config("", "");
+        cfg.logging.file_path = path.to_str().unwrap().to_string();
+        cfg.logging.max_memory_logs = 3;
+        let initial_logs = load_initial_logs(&cfg.logging, None).await.unwrap();
+        let storage: SharedStorage = Arc::new(MemoryStorage::new(
+            Arc::new(RwLock::new(initial_logs)),
+            cfg.logging.max_memory_logs,
+            Gauge::new("logs_in_memory", "logs_in_memory").unwrap(),
+        ));
+        let app = test::init_service(test_app(cfg, storage).await).await;
+
+        let req = test::TestRequest::get().uri("/v1/logs").to_request();
+        let page: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let logs: Vec<LogEntry> = serde_json::from_value(page["logs"].clone()).unwrap();
+        // The oldest entry is dropped to fit max_memory_logs and the rest are renumbered
+        assert_eq!(logs.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), ["two", "three", "four"]);
+        assert_eq!(logs.iter().map(|e| e.id).collect::<Vec<_>>(), [1, 2, 3]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_web::test]
+    async fn purge_removes_only_matching_level() {
+        let cfg = test_config("admin_token = \"admin\"", "");
+        let storage = memory_storage(&cfg);
+        let app = test::init_service(test_app(cfg, storage.clone()).await).await;
+
+        let entries = [("DEBUG", "cache miss"), ("INFO", "started"), ("DEBUG", "cache hit"), ("WARN", "slow query")];
+        for (level, message) in entries {
+            let req = test::TestRequest::post().uri("/v1/logs").set_json(entry_json("test", level, message));
+            assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::ACCEPTED);
+        }
+        wait_for_logs(&storage, entries.len()).await;
+
+        let purge = || test::TestRequest::post().uri("/v1/logs/purge").set_json(serde_json::json!({ "level": "DEBUG" }));
+        assert_eq!(test::call_service(&app, purge().to_request()).await.status(), StatusCode::UNAUTHORIZED);
+        let req = purge().insert_header(("Authorization", "Bearer admin")).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body, serde_json::json!({ "purged": 2 }));
+
+        let rest = storage.query(&FilterParams::default()).await.unwrap();
+        let rest: Vec<_> = rest.iter().map(|e| (e.level.as_str(), e.message.as_str())).collect();
+        assert_eq!(rest, [("INFO", "started"), ("WARN", "slow query")]);
+    }
+
+    #[actix_web::test]
+    async fn metrics_include_request_durations() {
+        let cfg = test_config("", "");
+        let app = test::init_service(test_app(cfg.clone(), memory_storage(&cfg)).await).await;
+
+        let req = test::TestRequest::get().uri("/v1/logs/42").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get().uri("/v1/metrics").to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(body.contains("# TYPE http_request_duration_seconds histogram"), "{}", body);
+        // Labelled by route pattern rather than path
+        assert!(
+            body.contains(r#"http_request_duration_seconds_count{method="GET",route="/v1/logs/{id}",status="404"} 1"#),
+            "{}",
+            body
+        );
+    }
+
+    #[actix_web::test]
+    async fn responses_are_gzipped_on_request() {
+        let cfg = test_config("compress_level = 6", "");
+        let storage = memory_storage(&cfg);
+        let entries: Vec<LogEntry> = (1..=50)
+            .map(|id| {
+                let mut entry: LogEntry = serde_json::from_value(entry_json("orders", "INFO", "order placed")).unwrap();
+                entry.id = id;
+                entry
+            })
+            .collect();
+        storage.insert_many(&entries).await.unwrap();
+        let app = test::init_service(
+            test_app(cfg.clone(), storage)
+                .await
+                .wrap(GzipLevelMiddleware::new(cfg.server.compress_level))
+                .wrap(Compress::default()),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/v1/logs").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.headers().get(header::CONTENT_ENCODING).is_none());
+        let plain = test::read_body(resp).await;
+
+        let req = test::TestRequest::get().uri("/v1/logs").insert_header((header::ACCEPT_ENCODING, "gzip")).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+        let gzipped = test::read_body(resp).await;
+        assert!(gzipped.len() < plain.len(), "{} >= {}", gzipped.len(), plain.len());
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&gzipped[..]).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, plain);
+    }
+
+    #[actix_web::test]
+    async fn client_cannot_write_into_another_namespace() {
+        let cfg = test_config("api_key = \"global\"\n[server.namespaces]\n\"team-a-key\" = \"team-a\"", "");
+        let storage = memory_storage(&cfg);
+        let app = test::init_service(test_app(cfg, storage.clone()).await).await;
+
+        let mut entry = entry_json("billing", "INFO", "not for team-a");
+        entry["namespace"] = "team-a".into();
+        let req = test::TestRequest::post().uri("/v1/logs").insert_header(("X-API-Key", "global")).set_json(entry);
+        assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::ACCEPTED);
+        assert_eq!(wait_for_logs(&storage, 1).await[0].namespace, None);
+
+        let logs = |key: Option<&str>| {
+            let req = test::TestRequest::get().uri("/v1/logs");
+            match key {
+                Some(key) => req.insert_header(("X-API-Key", key.to_string())),
+                None => req,
+            }
+            .to_request()
+        };
+        let page: serde_json::Value = test::call_and_read_body_json(&app, logs(Some("team-a-key"))).await;
+        assert_eq!(page["logs"], serde_json::json!([]));
+        let page: serde_json::Value = test::call_and_read_body_json(&app, logs(None)).await;
+        assert_eq!(page["logs"][0]["message"], "not for team-a");
+    }
 }
@@ -0,0 +1,565 @@
+use actix_web::body::{self, BodySize, EitherBody, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::{error, Error, HttpMessage, HttpResponse};
+use bytes::BytesMut;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::future::{ready, LocalBoxFuture, Ready};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use prometheus::HistogramVec;
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::cors::CorsConfig;
+use crate::namespace::Namespaces;
+
+// Rejects requests whose X-API-Key header is neither api_key nor a namespaced key; a no-op when no
+// key is configured
+#[derive(Clone)]
+pub(crate) struct ApiKeyMiddleware {
+    api_key: Option<Rc<str>>,
+    namespaces: Namespaces,
+}
+
+impl ApiKeyMiddleware {
+    pub(crate) fn new(api_key: Option<&str>, namespaces: Namespaces) -> Self {
+        Self { api_key: api_key.map(Rc::from), namespaces }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = ApiKeyService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyService {
+            service: Rc::new(service),
+            api_key: self.api_key.clone(),
+            namespaces: self.namespaces.clone(),
+        }))
+    }
+}
+
+pub(crate) struct ApiKeyService<S> {
+    service: Rc<S>,
+    api_key: Option<Rc<str>>,
+    namespaces: Namespaces,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let authorized = (self.api_key.is_none() && self.namespaces.is_empty())
+            || req
+                .headers()
+                .get("X-API-Key")
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|provided| {
+                    // Both run whatever the first finds, so the timing does not say which key matched
+                    let global = self.api_key.as_deref().is_some_and(|key| secret_eq(key, provided));
+                    let namespaced = self.namespaces.lookup(provided).is_some();
+                    global | namespaced
+                });
+
+        if !authorized {
+            // Never echo the expected or provided key back to the caller
+            let resp = HttpResponse::Unauthorized().json(serde_json::json!({ "error": "unauthorized" }));
+            return Box::pin(async move { Ok(req.into_response(resp).map_into_right_body()) });
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+    }
+}
+
+// Compare a secret with what a caller sent without leaking, through timing, how much of it matched
+pub(crate) fn secret_eq(expected: &str, provided: &str) -> bool {
+    bool::from(expected.as_bytes().ct_eq(provided.as_bytes()))
+}
+
+// Furthest X-Timestamp may be from server time, either way
+const HMAC_MAX_SKEW_SECS: i64 = 30;
+// A signature stops passing the X-Timestamp check at most this long after it is first accepted
+const REPLAY_TTL: Duration = Duration::from_secs(2 * HMAC_MAX_SKEW_SECS as u64);
+
+// The key whose signature HmacAuthMiddleware accepted, which picks the request's namespace
+#[derive(Clone)]
+pub(crate) struct SignedKey(pub(crate) String);
+
+// Signatures accepted within REPLAY_TTL, shared by every worker; a repeat is a replay
+#[derive(Clone, Default)]
+pub(crate) struct SeenSignatures(Arc<Mutex<SeenState>>);
+
+#[derive(Default)]
+struct SeenState {
+    signatures: HashSet<String>,
+    // Oldest first, for dropping expired signatures from the set
+    accepted_at: VecDeque<(Instant, String)>,
+}
+
+impl SeenSignatures {
+    // Records a signature, returning false if it was already seen
+    fn insert(&self, signature: String) -> bool {
+        let mut state = self.0.lock().unwrap();
+        let now = Instant::now();
+        while let Some((at, _)) = state.accepted_at.front()
+            && now.duration_since(*at) > REPLAY_TTL
+        {
+            let (_, expired) = state.accepted_at.pop_front().unwrap();
+            state.signatures.remove(&expired);
+        }
+        if !state.signatures.insert(signature.clone()) {
+            return false;
+        }
+        state.accepted_at.push_back((now, signature));
+        true
+    }
+}
+
+// With [server] hmac_auth, requests prove they hold api_key or a namespaced key instead of sending
+// it: X-Signature is the hex HMAC-SHA256, under the key, of method + path and query + X-Timestamp +
+// the hex sha256 of the body. X-Timestamp is Unix seconds within 30 s of server time
+#[derive(Clone)]
+pub(crate) struct HmacAuthMiddleware {
+    keys: Rc<[String]>,
+    seen: SeenSignatures,
+    max_body: usize,
+}
+
+impl HmacAuthMiddleware {
+    // max_body bounds what is buffered for hashing, before the request is known to be genuine
+    pub(crate) fn new(api_key: Option<&str>, namespaces: &Namespaces, seen: SeenSignatures, max_body: usize) -> Self {
+        let keys = api_key.into_iter().chain(namespaces.keys()).map(str::to_string).collect();
+        Self { keys, seen, max_body }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HmacAuthMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HmacAuthService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HmacAuthService {
+            service: Rc::new(service),
+            keys: self.keys.clone(),
+            seen: self.seen.clone(),
+            max_body: self.max_body,
+        }))
+    }
+}
+
+pub(crate) struct HmacAuthService<S> {
+    service: Rc<S>,
+    keys: Rc<[String]>,
+    seen: SeenSignatures,
+    max_body: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for HmacAuthService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let keys = self.keys.clone();
+        let seen = self.seen.clone();
+        let max_body = self.max_body;
+
+        Box::pin(async move {
+            // The body is hashed as sent, then handed back for the handler to read
+            let mut payload = req.take_payload();
+            let mut body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk?;
+                if body.len() + chunk.len() > max_body {
+                    let resp = HttpResponse::PayloadTooLarge().json(serde_json::json!({
+                        "error": "body too large",
+                        "max_body_bytes": max_body
+                    }));
+                    return Ok(req.into_response(resp).map_into_right_body());
+                }
+                body.extend_from_slice(&chunk);
+            }
+            let body = body.freeze();
+
+            match check_signature(&req, &body, &keys, &seen) {
+                Ok(key) => {
+                    req.extensions_mut().insert(SignedKey(key));
+                    req.set_payload(body.into());
+                    Ok(service.call(req).await?.map_into_left_body())
+                }
+                Err(reason) => {
+                    let resp = HttpResponse::Unauthorized().json(serde_json::json!({ "error": reason }));
+                    Ok(req.into_response(resp).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+// The key that signed the request, or why it is refused
+fn check_signature(
+    req: &ServiceRequest,
+    body: &[u8],
+    keys: &[String],
+    seen: &SeenSignatures,
+) -> Result<String, &'static str> {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    let timestamp = header("X-Timestamp").ok_or("missing X-Timestamp")?;
+    let signature = header("X-Signature").ok_or("missing X-Signature")?;
+
+    let sent_at: i64 = timestamp.trim().parse().map_err(|_| "X-Timestamp must be Unix seconds")?;
+    if (chrono::Utc::now().timestamp() - sent_at).abs() > HMAC_MAX_SKEW_SECS {
+        return Err("X-Timestamp is more than 30 s from server time");
+    }
+    let provided = hex::decode(signature.trim()).map_err(|_| "X-Signature must be hex")?;
+
+    // The query string is signed too, or ?service=x could be stripped to widen a DELETE
+    let path = req.uri().path_and_query().map_or(req.path(), |p| p.as_str());
+    let signed = format!("{}{}{}{}", req.method(), path, timestamp, hex::encode(Sha256::digest(body)));
+    let key = keys
+        .iter()
+        .find(|key| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length");
+            mac.update(signed.as_bytes());
+            bool::from(mac.finalize().into_bytes().as_slice().ct_eq(&provided))
+        })
+        .ok_or("bad signature")?;
+
+    // Checked last so a forged request cannot burn a genuine signature
+    if !seen.insert(hex::encode(&provided)) {
+        return Err("replayed signature");
+    }
+    Ok(key.clone())
+}
+
+// Records every request's latency in http_request_duration_seconds{method, route, status}
+#[derive(Clone)]
+pub(crate) struct PromMiddleware {
+    histogram: HistogramVec,
+}
+
+impl PromMiddleware {
+    pub(crate) fn new(histogram: HistogramVec) -> Self {
+        Self { histogram }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for PromMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = PromService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(PromService {
+            service: Rc::new(service),
+            histogram: self.histogram.clone(),
+        }))
+    }
+}
+
+pub(crate) struct PromService<S> {
+    service: Rc<S>,
+    histogram: HistogramVec,
+}
+
+impl<S, B> Service<ServiceRequest> for PromService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let method = req.method().to_string();
+        // The route pattern rather than the path, so /logs/{id} is a single series
+        let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+        let histogram = self.histogram.clone();
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let result = service.call(req).await;
+            let status = match &result {
+                Ok(res) => res.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+
+            histogram
+                .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+                .observe(start.elapsed().as_secs_f64());
+            result
+        })
+    }
+}
+
+// Answers CORS preflights from origins outside the [cors] list with 403 before they reach actix-cors,
+// which would answer 400; a no-op when CORS is not configured
+#[derive(Clone)]
+pub(crate) struct CorsPreflightMiddleware {
+    cors: Option<Rc<CorsConfig>>,
+}
+
+impl CorsPreflightMiddleware {
+    pub(crate) fn new(cors: Option<&CorsConfig>) -> Self {
+        Self { cors: cors.cloned().map(Rc::new) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CorsPreflightMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CorsPreflightService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CorsPreflightService {
+            service: Rc::new(service),
+            cors: self.cors.clone(),
+        }))
+    }
+}
+
+pub(crate) struct CorsPreflightService<S> {
+    service: Rc<S>,
+    cors: Option<Rc<CorsConfig>>,
+}
+
+impl<S, B> Service<ServiceRequest> for CorsPreflightService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let headers = req.headers();
+        let rejected = self.cors.as_deref().is_some_and(|cors| {
+            req.method() == Method::OPTIONS
+                && headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD)
+                && headers
+                    .get(header::ORIGIN)
+                    .is_some_and(|origin| !origin.to_str().is_ok_and(|origin| cors.allows(origin)))
+        });
+
+        if rejected {
+            let resp = HttpResponse::Forbidden().json(serde_json::json!({ "error": "origin not allowed" }));
+            return Box::pin(async move { Ok(req.into_response(resp).map_into_right_body()) });
+        }
+
+        let service = self.service.clone();
+        Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) })
+    }
+}
+
+pub(crate) const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// The id RequestIdMiddleware gave a request, readable by handlers as web::ReqData<RequestId>
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RequestId(pub(crate) Uuid);
+
+// Tags each request with the client's X-Request-ID when it is a valid UUID, otherwise a fresh v4
+// one, echoes it in the response and records it on a span around everything the request logs
+#[derive(Clone)]
+pub(crate) struct RequestIdMiddleware;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestIdService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdService { service: Rc::new(service) }))
+    }
+}
+
+pub(crate) struct RequestIdService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| Uuid::parse_str(v.trim()).ok())
+            .unwrap_or_else(Uuid::new_v4);
+        req.extensions_mut().insert(RequestId(id));
+
+        let span = tracing::info_span!("request", request_id = %id);
+        let service = self.service.clone();
+        Box::pin(
+            async move {
+                let mut res = service.call(req).await?;
+                if let Ok(value) = HeaderValue::from_str(&id.to_string()) {
+                    res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                Ok(res)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+// actix's Compress always gzips at the fastest level. With a level configured, complete bodies are
+// gzipped here at that level first, which Compress then leaves alone; streams are still its job
+#[derive(Clone)]
+pub(crate) struct GzipLevelMiddleware {
+    level: Option<u32>,
+}
+
+impl GzipLevelMiddleware {
+    pub(crate) fn new(level: Option<u32>) -> Self {
+        Self { level }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for GzipLevelMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, Vec<u8>>>;
+    type Error = Error;
+    type Transform = GzipLevelService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(GzipLevelService {
+            service: Rc::new(service),
+            level: self.level,
+        }))
+    }
+}
+
+pub(crate) struct GzipLevelService<S> {
+    service: Rc<S>,
+    level: Option<u32>,
+}
+
+impl<S, B> Service<ServiceRequest> for GzipLevelService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, Vec<u8>>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let level = self.level.filter(|_| accepts_gzip(req.headers()));
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            let Some(level) = level else {
+                return Ok(res.map_into_left_body());
+            };
+            if res.headers().contains_key(header::CONTENT_ENCODING)
+                || !matches!(res.response().body().size(), BodySize::Sized(n) if n > 0)
+            {
+                return Ok(res.map_into_left_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let (mut head, body) = res.into_parts();
+            let body = body::to_bytes(body).await.map_err(|e| error::ErrorInternalServerError(e.into()))?;
+            let mut encoder = GzEncoder::new(Vec::with_capacity(body.len() / 2), Compression::new(level));
+            encoder.write_all(&body)?;
+            let gzipped = encoder.finish()?;
+
+            head.headers_mut().insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+            head.headers_mut().append(header::VARY, HeaderValue::from_static("accept-encoding"));
+            Ok(ServiceResponse::new(req, head.set_body(gzipped)).map_into_right_body())
+        })
+    }
+}
+
+// Whether Accept-Encoding lists gzip (or *) without refusing it with q=0
+fn accepts_gzip(headers: &header::HeaderMap) -> bool {
+    headers.get_all(header::ACCEPT_ENCODING).filter_map(|v| v.to_str().ok()).flat_map(|v| v.split(',')).any(|coding| {
+        let mut parts = coding.split(';').map(str::trim);
+        let name = parts.next().unwrap_or_default();
+        let refused = parts.any(|p| p.strip_prefix("q=").is_some_and(|q| q.parse::<f32>().is_ok_and(|q| q == 0.0)));
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+    })
+}
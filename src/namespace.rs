@@ -0,0 +1,83 @@
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ready, Ready};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use crate::middleware::{secret_eq, SignedKey};
+use crate::LogEntry;
+
+// Where entries without a namespace belong, and what requests without a namespaced key see
+pub(crate) const DEFAULT_NAMESPACE: &str = "default";
+
+// [server] namespaces: which namespace each API key ingests into and reads from
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Namespaces(Arc<HashMap<String, String>>);
+
+impl Namespaces {
+    pub(crate) fn new(by_key: HashMap<String, String>) -> Self {
+        Self(Arc::new(by_key))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // The namespace an API key is tied to. Every key is compared in constant time, so how long this
+    // takes does not tell a caller how close they came to one
+    pub(crate) fn lookup(&self, key: &str) -> Option<&str> {
+        let mut found = None;
+        for (candidate, namespace) in self.0.iter() {
+            if secret_eq(candidate, key) {
+                found = Some(namespace.as_str());
+            }
+        }
+        found
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+}
+
+// The namespace a request acts in, from the key that signed it or else its X-API-Key; extracting it
+// never fails
+#[derive(Debug, Clone)]
+pub(crate) struct Namespace {
+    pub(crate) name: String,
+    // Whether the key named it; ingested entries go there, or to "default" when it did not
+    pub(crate) assigned: bool,
+}
+
+impl Namespace {
+    // Entries from a namespaced key are forced into its namespace and all others into "default", so
+    // a client cannot write into a tenant by naming it
+    pub(crate) fn assign(&self, entry: &mut LogEntry) {
+        entry.namespace = self.assigned.then(|| self.name.clone());
+    }
+}
+
+impl FromRequest for Namespace {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let assigned = req.app_data::<web::Data<Namespaces>>().and_then(|namespaces| {
+            if let Some(SignedKey(key)) = req.extensions().get::<SignedKey>() {
+                return namespaces.0.get(key).cloned();
+            }
+            let key = req.headers().get("X-API-Key")?.to_str().ok()?;
+            namespaces.lookup(key).map(str::to_string)
+        });
+        ready(Ok(match assigned {
+            Some(name) => Namespace { name, assigned: true },
+            None => Namespace { name: DEFAULT_NAMESPACE.to_string(), assigned: false },
+        }))
+    }
+}
+
+// The namespace an entry is counted in
+pub(crate) fn namespace_of(entry: &LogEntry) -> &str {
+    entry.namespace.as_deref().unwrap_or(DEFAULT_NAMESPACE)
+}
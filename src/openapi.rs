@@ -0,0 +1,349 @@
+// The response shapes here only describe what the handlers build with json!, nothing constructs them
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::LogEntry;
+
+// The spec served at GET /openapi.json and behind GET /swagger-ui/
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Log Aggregator", description = "Ingest, query and stream structured logs"),
+    paths(
+        crate::health,
+        crate::ready,
+        crate::post_log,
+        crate::get_logs,
+        crate::clear_logs,
+        crate::post_logs_batch,
+        crate::import_logs,
+        crate::purge_logs,
+        crate::verify_logs,
+        crate::get_stats,
+        crate::get_logs_diff,
+        crate::get_logs_tail,
+        crate::get_log_count,
+        crate::export_logs,
+        crate::get_logs_by_trace,
+        crate::get_similar_logs,
+        crate::get_service_graph,
+        crate::get_services,
+        crate::get_levels,
+        crate::get_fields,
+        crate::get_logs_aggregate,
+        crate::get_top_errors,
+        crate::get_error_rate,
+        crate::get_service_health,
+        crate::logs_stream,
+        crate::ws::logs_ws,
+        crate::get_log_by_id,
+        crate::delete_log,
+        crate::patch_log,
+        crate::metrics,
+    ),
+    components(schemas(
+        LogEntry,
+        crate::PurgeRequest,
+        crate::LogPatch,
+        ErrorResponse,
+        LevelRejected,
+        LogNotFound,
+        LimitExceeded,
+        NotReady,
+        Health,
+        Ready,
+        Accepted,
+        LogPage,
+        BatchResult,
+        BatchRejection,
+        ImportResult,
+        ImportError,
+        Count,
+        Purged,
+        Verified,
+        StatsResponse,
+        StatsTotals,
+        StatsBuckets,
+        StatsBucket,
+        LogDiff,
+        WindowVolume,
+        VolumeDelta,
+        NamedCount,
+        Services,
+        Levels,
+        Fields,
+        FieldInfo,
+        AggregateGroup,
+        TopError,
+        ErrorRatePoint,
+        ServiceHealth,
+        SimilarLogs,
+        SimilarEntry,
+        ServiceGraph,
+        ServiceEdge,
+    )),
+    modifiers(&SecuritySchemes),
+)]
+pub(crate) struct ApiDoc;
+
+// X-API-Key guards the write endpoints when [server] api_keys is set, a bearer admin_token the
+// destructive ones
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_default();
+        components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+#[derive(ToSchema)]
+pub(crate) struct ErrorResponse {
+    error: String,
+}
+
+// 422 from POST /logs for a level outside the accepted set
+#[derive(ToSchema)]
+pub(crate) struct LevelRejected {
+    error: String,
+    accepted: Vec<String>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct LogNotFound {
+    error: String,
+    id: u64,
+}
+
+// 413s name the limit that was hit, one of these fields depending on the endpoint
+#[derive(ToSchema)]
+pub(crate) struct LimitExceeded {
+    error: String,
+    max_body_bytes: Option<usize>,
+    max_batch_size: Option<usize>,
+    import_max_mb: Option<u64>,
+    max_bytes: Option<usize>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct NotReady {
+    #[schema(example = "not_ready")]
+    status: String,
+    reason: String,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Health {
+    #[schema(example = "ok")]
+    status: String,
+    uptime_secs: u64,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Ready {
+    #[schema(example = "ready")]
+    status: String,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Accepted {
+    #[schema(example = "Log accepted")]
+    message: String,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct LogPage {
+    total: usize,
+    offset: usize,
+    limit: usize,
+    logs: Vec<LogEntry>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct BatchResult {
+    accepted: usize,
+    rejected: Vec<BatchRejection>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct BatchRejection {
+    index: usize,
+    reason: String,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct ImportResult {
+    accepted: usize,
+    rejected: usize,
+    errors: Vec<ImportError>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct ImportError {
+    line: usize,
+    error: String,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Count {
+    count: usize,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Purged {
+    purged: usize,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Verified {
+    ok: usize,
+    tampered: usize,
+    // Lines written before signing_key was set, or by a server without one
+    missing_sig: usize,
+}
+
+// GET /logs/stats answers with totals, or with buckets when a window is given
+#[derive(ToSchema)]
+#[serde(untagged)]
+pub(crate) enum StatsResponse {
+    Totals(StatsTotals),
+    Buckets(StatsBuckets),
+}
+
+#[derive(ToSchema)]
+pub(crate) struct StatsTotals {
+    by_level: HashMap<String, usize>,
+    by_service: HashMap<String, usize>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct StatsBuckets {
+    buckets: Vec<StatsBucket>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct StatsBucket {
+    start: String,
+    by_level: HashMap<String, usize>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct LogDiff {
+    baseline: WindowVolume,
+    current: WindowVolume,
+    diff: VolumeDelta,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct WindowVolume {
+    total: usize,
+    by_level: HashMap<String, usize>,
+    by_service: HashMap<String, usize>,
+}
+
+// current minus baseline, negative where volume dropped
+#[derive(ToSchema)]
+pub(crate) struct VolumeDelta {
+    total_delta: i64,
+    by_level: HashMap<String, i64>,
+    by_service: HashMap<String, i64>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct NamedCount {
+    name: String,
+    count: usize,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Services {
+    services: Vec<NamedCount>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Levels {
+    levels: Vec<NamedCount>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct Fields {
+    metadata_keys: Vec<FieldInfo>,
+}
+
+// type is the JSON type most of the key's values have, example the first such value
+#[derive(ToSchema)]
+pub(crate) struct FieldInfo {
+    key: String,
+    #[schema(example = "string")]
+    r#type: String,
+    example: serde_json::Value,
+    count: usize,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct AggregateGroup {
+    value: String,
+    count: usize,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct TopError {
+    message: String,
+    count: usize,
+    service: String,
+    last_seen: String,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct ErrorRatePoint {
+    minute: String,
+    error_count: usize,
+    total_count: usize,
+    rate: f64,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct SimilarLogs {
+    reference: LogEntry,
+    similar: Vec<SimilarEntry>,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct SimilarEntry {
+    entry: LogEntry,
+    score: f64,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct ServiceGraph {
+    nodes: Vec<String>,
+    edges: Vec<ServiceEdge>,
+    note: String,
+}
+
+// Traces in which from's first entry came before to's
+#[derive(ToSchema)]
+pub(crate) struct ServiceEdge {
+    from: String,
+    to: String,
+    count: usize,
+}
+
+#[derive(ToSchema)]
+pub(crate) struct ServiceHealth {
+    service: String,
+    health: f64,
+    error_count: usize,
+    total: usize,
+    window_secs: u32,
+    // "idle" without entries in the window (health is then 0.0), "degraded" below [logging]
+    // health_warn_threshold, absent otherwise
+    #[schema(example = "degraded")]
+    status: Option<String>,
+}
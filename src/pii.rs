@@ -0,0 +1,124 @@
+use regex::{NoExpand, Regex};
+use serde::Deserialize;
+
+use crate::LogEntry;
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct PiiConfig {
+    pub(crate) patterns: Vec<String>,
+    #[serde(default = "default_replacement")]
+    pub(crate) replacement: String,
+}
+
+fn default_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+// Compiled once at startup; an empty scrubber leaves entries untouched
+#[derive(Debug, Default)]
+pub(crate) struct PiiScrubber {
+    patterns: Vec<Regex>,
+    replacement: String,
+}
+
+impl PiiScrubber {
+    pub(crate) fn new(cfg: &PiiConfig) -> Result<Self, regex::Error> {
+        let patterns = cfg.patterns.iter().map(|p| Regex::new(p)).collect::<Result<_, _>>()?;
+        Ok(Self { patterns, replacement: cfg.replacement.clone() })
+    }
+
+    // Redact the message and every string inside metadata, however deeply nested
+    pub(crate) fn scrub(&self, entry: &mut LogEntry) {
+        if self.patterns.is_empty() {
+            return;
+        }
+
+        self.scrub_str(&mut entry.message);
+        if let Some(metadata) = entry.metadata.as_mut() {
+            for value in metadata.values_mut() {
+                self.scrub_value(value);
+            }
+        }
+    }
+
+    fn scrub_value(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => self.scrub_str(s),
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|v| self.scrub_value(v)),
+            serde_json::Value::Object(map) => map.values_mut().for_each(|v| self.scrub_value(v)),
+            _ => {}
+        }
+    }
+
+    fn scrub_str(&self, s: &mut String) {
+        for re in &self.patterns {
+            if let std::borrow::Cow::Owned(replaced) = re.replace_all(s, NoExpand(&self.replacement)) {
+                *s = replaced;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const EMAIL: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+
+    fn entry(message: &str, metadata: serde_json::Value) -> LogEntry {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "service": "auth",
+            "level": "INFO",
+            "message": message,
+            "metadata": metadata,
+        }))
+        .unwrap()
+    }
+
+    fn scrubber(patterns: &[&str], replacement: &str) -> PiiScrubber {
+        PiiScrubber::new(&PiiConfig {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            replacement: replacement.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn scrubs_emails_in_message_and_metadata() {
+        let mut log = entry(
+            "password reset for jane.doe+test@example.com requested",
+            serde_json::json!({ "user": "jane@example.org", "contacts": [{ "cc": "ops@example.net" }], "attempts": 2 }),
+        );
+        scrubber(&[EMAIL], "[REDACTED]").scrub(&mut log);
+
+        assert_eq!(log.message, "password reset for [REDACTED] requested");
+        let expected: HashMap<String, serde_json::Value> = serde_json::from_value(serde_json::json!({
+            "user": "[REDACTED]",
+            "contacts": [{ "cc": "[REDACTED]" }],
+            "attempts": 2,
+        }))
+        .unwrap();
+        assert_eq!(log.metadata, Some(expected));
+    }
+
+    #[test]
+    fn replacement_is_taken_literally() {
+        let mut log = entry("mail a@b.io", serde_json::Value::Null);
+        scrubber(&[EMAIL], "$0").scrub(&mut log);
+        assert_eq!(log.message, "mail $0");
+    }
+
+    #[test]
+    fn leaves_entries_alone_without_patterns() {
+        let mut log = entry("mail a@b.io", serde_json::Value::Null);
+        PiiScrubber::default().scrub(&mut log);
+        assert_eq!(log.message, "mail a@b.io");
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(PiiScrubber::new(&PiiConfig { patterns: vec!["(".to_string()], replacement: default_replacement() }).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use lru::LruCache;
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+use crate::namespace::Namespace;
+use crate::LogEntry;
+
+// Distinct queries remembered at once, least recently used dropped first
+const QUERY_CACHE_CAPACITY: NonZeroUsize = NonZeroUsize::new(256).unwrap();
+
+// One GET /logs page and its total, with when it was computed
+type CachedPage = (Vec<LogEntry>, usize, Instant);
+
+// Recent GET /logs results by canonical query string, so polling dashboards skip the store
+#[derive(Clone)]
+pub(crate) struct QueryCache {
+    pages: Arc<Mutex<LruCache<String, CachedPage>>>,
+    ttl: Duration,
+}
+
+impl QueryCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self { pages: Arc::new(Mutex::new(LruCache::new(QUERY_CACHE_CAPACITY))), ttl }
+    }
+
+    // Only explicitly paginated queries are cached, per namespace; None means skip the cache
+    pub(crate) fn key(&self, query: &HashMap<String, String>, namespace: &Namespace) -> Option<String> {
+        if self.ttl.is_zero() || !query.contains_key("limit") || !query.contains_key("offset") {
+            return None;
+        }
+        let sorted: BTreeMap<_, _> = query.iter().collect();
+        let pairs: Vec<String> = sorted
+            .into_iter()
+            .map(|(k, v)| format!("{}={}", urlencoding::encode(k), urlencoding::encode(v)))
+            .collect();
+        Some(format!("{}?{}", urlencoding::encode(&namespace.name), pairs.join("&")))
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<(Vec<LogEntry>, usize)> {
+        let mut pages = self.pages.lock().unwrap();
+        match pages.get(key) {
+            Some((logs, total, at)) if at.elapsed() < self.ttl => Some((logs.clone(), *total)),
+            Some(_) => {
+                pages.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub(crate) fn put(&self, key: String, logs: Vec<LogEntry>, total: usize) {
+        self.pages.lock().unwrap().put(key, (logs, total, Instant::now()));
+    }
+}
@@ -0,0 +1,65 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+// Buckets untouched for this long are dropped by the sweeper
+pub(crate) const BUCKET_IDLE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct RateLimitConfig {
+    pub(crate) requests_per_second: f64,
+    pub(crate) burst: usize,
+}
+
+// Per-client token bucket, refilled lazily whenever it is checked
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    last_refill: Instant,
+    tokens: f64,
+    requests_per_second: f64,
+    burst: usize,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(cfg: &RateLimitConfig) -> Self {
+        Self {
+            last_refill: Instant::now(),
+            tokens: cfg.burst as f64,
+            requests_per_second: cfg.requests_per_second,
+            burst: cfg.burst,
+        }
+    }
+
+    // Take one token, or report how long until one becomes available
+    pub(crate) fn try_take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.requests_per_second).min(self.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else if self.requests_per_second > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.requests_per_second))
+        } else {
+            Err(BUCKET_IDLE_TTL)
+        }
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_refill.elapsed()
+    }
+}
+
+pub(crate) type RateLimitBuckets = Arc<Mutex<HashMap<IpAddr, TokenBucket>>>;
+
+// Periodically drop buckets for clients that have gone quiet
+pub(crate) async fn sweep_idle_buckets(buckets: RateLimitBuckets) {
+    loop {
+        tokio::time::sleep(BUCKET_IDLE_TTL).await;
+        buckets.lock().unwrap().retain(|_, bucket| bucket.idle_for() < BUCKET_IDLE_TTL);
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::time::{Duration, Instant};
+
+use crate::bus::Sink;
+use crate::{LogEntry, LogEvent};
+
+// The room every entry goes to, served by the shared broadcaster rather than the map; a service
+// called "global" therefore has no room of its own
+pub(crate) const GLOBAL_ROOM: &str = "global";
+
+struct Room {
+    sender: broadcast::Sender<LogEvent>,
+    // When the last subscriber was seen leaving, None while anyone is listening
+    idle_since: Option<Instant>,
+}
+
+// Per-service broadcast channels for GET /logs/stream?room=<service>, created on first use
+#[derive(Clone)]
+pub(crate) struct Rooms {
+    rooms: Arc<Mutex<HashMap<String, Room>>>,
+    capacity: usize,
+}
+
+impl Rooms {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self { rooms: Arc::default(), capacity: capacity.max(1) }
+    }
+
+    fn open<'a>(&self, rooms: &'a mut HashMap<String, Room>, name: &str) -> &'a mut Room {
+        rooms.entry(name.to_string()).or_insert_with(|| Room {
+            sender: broadcast::channel(self.capacity).0,
+            idle_since: Some(Instant::now()),
+        })
+    }
+
+    pub(crate) fn subscribe(&self, name: &str) -> broadcast::Receiver<LogEvent> {
+        let mut rooms = self.rooms.lock().unwrap();
+        let room = self.open(&mut rooms, name);
+        room.idle_since = None;
+        room.sender.subscribe()
+    }
+
+    fn send(&self, name: &str, event: LogEvent) {
+        let mut rooms = self.rooms.lock().unwrap();
+        let _ = self.open(&mut rooms, name).sender.send(event);
+    }
+
+    // Drop rooms that have had no subscriber for longer than ttl, checking every ttl
+    pub(crate) async fn sweep_idle(self, ttl: Duration) {
+        let ttl = ttl.max(Duration::from_secs(1));
+        loop {
+            tokio::time::sleep(ttl).await;
+            let now = Instant::now();
+            self.rooms.lock().unwrap().retain(|_, room| {
+                if room.sender.receiver_count() > 0 {
+                    room.idle_since = None;
+                    return true;
+                }
+                let idle_since = *room.idle_since.get_or_insert(now);
+                now.duration_since(idle_since) < ttl
+            });
+        }
+    }
+}
+
+// The global room is fed by BroadcastSink, each service's room from here
+impl Sink for Rooms {
+    fn stored(&self, entry: &LogEntry, payload: &str, updated: bool) {
+        self.send(&entry.service, LogEvent { payload: payload.to_string(), updated });
+    }
+}
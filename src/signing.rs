@@ -0,0 +1,57 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Field appended to each persisted line when [logging] signing_key is set
+const SIG_FIELD: &str = "_sig";
+
+// What POST /logs/verify found for one persisted line
+pub(crate) enum LineCheck {
+    Ok,
+    Tampered,
+    MissingSig,
+}
+
+fn mac(key: &str) -> HmacSha256 {
+    HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC takes keys of any length")
+}
+
+// Append "_sig": hex HMAC-SHA256 of the entry's JSON as the last field, so verification can
+// recover the exact signed bytes without re-serialising (metadata key order is not stable)
+pub(crate) fn sign_line(key: &str, json: &str) -> String {
+    let mut mac = mac(key);
+    mac.update(json.as_bytes());
+    let sig = hex::encode(mac.finalize().into_bytes());
+    let fields = json.strip_suffix('}').unwrap_or(json);
+    format!("{},\"{}\":\"{}\"}}", fields, SIG_FIELD, sig)
+}
+
+// Lines that are not JSON objects, or whose _sig is anywhere but last, count as tampered
+pub(crate) fn check_line(key: &str, line: &str) -> LineCheck {
+    let Ok(serde_json::Value::Object(fields)) = serde_json::from_str::<serde_json::Value>(line) else {
+        return LineCheck::Tampered;
+    };
+    if !fields.contains_key(SIG_FIELD) {
+        return LineCheck::MissingSig;
+    }
+
+    let signed = line
+        .trim_end()
+        .strip_suffix("\"}")
+        .and_then(|rest| rest.rsplit_once(&format!(",\"{}\":\"", SIG_FIELD)));
+    let Some((fields, sig)) = signed else {
+        return LineCheck::Tampered;
+    };
+    let Ok(sig) = hex::decode(sig) else {
+        return LineCheck::Tampered;
+    };
+
+    let mut mac = mac(key);
+    mac.update(fields.as_bytes());
+    mac.update(b"}");
+    match mac.verify_slice(&sig) {
+        Ok(()) => LineCheck::Ok,
+        Err(_) => LineCheck::Tampered,
+    }
+}
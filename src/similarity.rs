@@ -0,0 +1,22 @@
+use std::collections::HashSet;
+
+// Words per shingle; messages shorter than this are compared as a single shingle
+const WORD_NGRAM: usize = 2;
+
+// Lowercased word n-grams of a message, punctuation kept so "id=42" and "id=43" differ
+pub(crate) fn shingles(message: &str) -> HashSet<String> {
+    let words: Vec<String> = message.split_whitespace().map(str::to_lowercase).collect();
+    if words.len() <= WORD_NGRAM {
+        return HashSet::from([words.join(" ")]);
+    }
+    words.windows(WORD_NGRAM).map(|w| w.join(" ")).collect()
+}
+
+// |a ∩ b| / |a ∪ b|, 1 for two empty sets
+pub(crate) fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
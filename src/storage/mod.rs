@@ -0,0 +1,318 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use prometheus::Gauge;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::filters::FilterParams;
+use crate::{evict_overflow, LogDb, LogEntry};
+
+mod postgres;
+mod redis;
+mod sqlite;
+pub(crate) use postgres::{PostgresConfig, PostgresStorage};
+pub(crate) use self::redis::{RedisConfig, RedisStorage};
+pub(crate) use sqlite::SqliteStorage;
+
+// A backend that could not complete an operation
+#[derive(Debug)]
+pub(crate) enum StorageError {
+    Sqlx(sqlx::Error),
+    Redis(::redis::RedisError),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Sqlx(e) => write!(f, "database error: {}", e),
+            StorageError::Redis(e) => write!(f, "redis error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(e: sqlx::Error) -> Self {
+        StorageError::Sqlx(e)
+    }
+}
+
+impl From<::redis::RedisError> for StorageError {
+    fn from(e: ::redis::RedisError) -> Self {
+        StorageError::Redis(e)
+    }
+}
+
+// Where log entries live; every HTTP handler goes through this so backends are swappable
+#[async_trait]
+pub(crate) trait Storage: Send + Sync {
+    async fn insert(&self, entry: &LogEntry) -> Result<(), StorageError>;
+
+    // Store several entries at once, backends may do this atomically
+    async fn insert_many(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+        for entry in entries {
+            self.insert(entry).await?;
+        }
+        Ok(())
+    }
+
+    // Matching entries in insertion order
+    async fn query(&self, filters: &FilterParams) -> Result<Vec<LogEntry>, StorageError> {
+        let mut logs = Vec::new();
+        self.scan(filters, &mut |log| logs.push(log.clone())).await?;
+        Ok(logs)
+    }
+
+    async fn count(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        let mut count = 0;
+        self.scan(filters, &mut |_| count += 1).await?;
+        Ok(count)
+    }
+
+    // Visit matching entries in insertion order without collecting them
+    async fn scan(
+        &self,
+        filters: &FilterParams,
+        visit: &mut (dyn for<'e> FnMut(&'e LogEntry) + Send),
+    ) -> Result<(), StorageError>;
+
+    async fn get(&self, id: u64) -> Result<Option<LogEntry>, StorageError>;
+
+    // Returns whether an entry was removed
+    async fn delete(&self, id: u64) -> Result<bool, StorageError>;
+
+    // Remove every entry, or only those of one service
+    async fn clear(&self, service: Option<&str>) -> Result<(), StorageError>;
+
+    // Add by to an entry's count, returning the updated entry if it still exists
+    async fn increment_count(&self, id: u64, by: usize) -> Result<Option<LogEntry>, StorageError>;
+
+    // Rewrite an entry's message and metadata with apply, returning the result if the entry exists;
+    // backends only persist those two fields
+    async fn update(
+        &self,
+        id: u64,
+        apply: &(dyn for<'e> Fn(&'e mut LogEntry) + Send + Sync),
+    ) -> Result<Option<LogEntry>, StorageError>;
+
+    // Remove every matching entry; returns how many went
+    async fn purge(&self, filters: &FilterParams) -> Result<usize, StorageError>;
+
+    // Drop entries timestamped before cutoff, keeping any whose timestamp does not parse; returns how many went
+    async fn evict_older_than(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError>;
+
+    // A trivial round trip, checked at startup before the port is bound; memory always answers
+    async fn health_check(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+}
+
+pub(crate) type SharedStorage = Arc<dyn Storage>;
+
+// The original backend: a bounded in-memory ring, persisted to disk by persist_logs
+pub(crate) struct MemoryStorage {
+    db: LogDb,
+    max_memory_logs: usize,
+    // Mirrors the store's length after every change
+    size_gauge: Gauge,
+}
+
+impl MemoryStorage {
+    pub(crate) fn new(db: LogDb, max_memory_logs: usize, size_gauge: Gauge) -> Self {
+        Self { db, max_memory_logs, size_gauge }
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn insert(&self, entry: &LogEntry) -> Result<(), StorageError> {
+        let mut db_lock = self.db.write().await;
+        db_lock.push_back(entry.clone());
+        evict_overflow(&mut db_lock, self.max_memory_logs);
+        self.size_gauge.set(db_lock.len() as f64);
+        Ok(())
+    }
+
+    async fn insert_many(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+        let mut db_lock = self.db.write().await;
+        db_lock.extend(entries.iter().cloned());
+        evict_overflow(&mut db_lock, self.max_memory_logs);
+        self.size_gauge.set(db_lock.len() as f64);
+        Ok(())
+    }
+
+    async fn scan(
+        &self,
+        filters: &FilterParams,
+        visit: &mut (dyn for<'e> FnMut(&'e LogEntry) + Send),
+    ) -> Result<(), StorageError> {
+        let db_lock = self.db.read().await;
+        for log in db_lock.iter().filter(|log| filters.matches(log)) {
+            visit(log);
+        }
+        Ok(())
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<LogEntry>, StorageError> {
+        let db_lock = self.db.read().await;
+        Ok(db_lock.iter().find(|log| log.id == id).cloned())
+    }
+
+    async fn delete(&self, id: u64) -> Result<bool, StorageError> {
+        let mut db_lock = self.db.write().await;
+
+        // remove (not swap_remove) so the store stays in insertion order
+        match db_lock.iter().position(|log| log.id == id) {
+            Some(pos) => {
+                db_lock.remove(pos);
+                self.size_gauge.set(db_lock.len() as f64);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn clear(&self, service: Option<&str>) -> Result<(), StorageError> {
+        let mut db_lock = self.db.write().await;
+        match service {
+            Some(service) => db_lock.retain(|log| log.service != service),
+            None => *db_lock = VecDeque::new(),
+        }
+        self.size_gauge.set(db_lock.len() as f64);
+        Ok(())
+    }
+
+    async fn increment_count(&self, id: u64, by: usize) -> Result<Option<LogEntry>, StorageError> {
+        let mut db_lock = self.db.write().await;
+        // Duplicates usually refer to a recent entry
+        let Some(entry) = db_lock.iter_mut().rev().find(|log| log.id == id) else {
+            return Ok(None);
+        };
+        entry.count += by;
+        Ok(Some(entry.clone()))
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        apply: &(dyn for<'e> Fn(&'e mut LogEntry) + Send + Sync),
+    ) -> Result<Option<LogEntry>, StorageError> {
+        let mut db_lock = self.db.write().await;
+        let Some(entry) = db_lock.iter_mut().rev().find(|log| log.id == id) else {
+            return Ok(None);
+        };
+        apply(entry);
+        Ok(Some(entry.clone()))
+    }
+
+    async fn purge(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        let mut db_lock = self.db.write().await;
+        let before = db_lock.len();
+        db_lock.retain(|log| !filters.matches(log));
+
+        self.size_gauge.set(db_lock.len() as f64);
+        Ok(before - db_lock.len())
+    }
+
+    async fn evict_older_than(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError> {
+        let is_old = |log: &LogEntry| DateTime::parse_from_rfc3339(&log.timestamp).is_ok_and(|ts| ts < cutoff);
+
+        let mut db_lock = self.db.write().await;
+        // Entries arrive in roughly chronological order, so the old ones sit at the front
+        let (front, back) = db_lock.as_slices();
+        let end = match front.partition_point(is_old) {
+            n if n < front.len() => n,
+            n => n + back.partition_point(is_old),
+        };
+
+        let kept: Vec<LogEntry> = db_lock.drain(..end).filter(|log| !is_old(log)).collect();
+        let evicted = end - kept.len();
+        for log in kept.into_iter().rev() {
+            db_lock.push_front(log);
+        }
+
+        self.size_gauge.set(db_lock.len() as f64);
+        Ok(evicted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn entry(id: u64) -> LogEntry {
+        LogEntry {
+            id,
+            timestamp: Utc::now().to_rfc3339(),
+            service: "orders".to_string(),
+            level: "INFO".to_string(),
+            message: format!("entry {}", id),
+            metadata: None,
+            trace_id: None,
+            span_id: None,
+            count: 1,
+            namespace: None,
+        }
+    }
+
+    fn memory_storage(db: LogDb, max_memory_logs: usize) -> MemoryStorage {
+        MemoryStorage::new(db, max_memory_logs, Gauge::new("logs_in_memory", "logs_in_memory").unwrap())
+    }
+
+    #[tokio::test]
+    async fn reads_do_not_block_each_other() {
+        let db: LogDb = Arc::default();
+        let storage = Arc::new(memory_storage(db.clone(), 10));
+        storage.insert(&entry(1)).await.unwrap();
+
+        // Another task holds a read lock for the whole test
+        let (held_tx, held_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let reader = tokio::spawn(async move {
+            let _guard = db.read().await;
+            held_tx.send(()).unwrap();
+            let _ = release_rx.await;
+        });
+        held_rx.await.unwrap();
+
+        let read = async { (storage.get(1).await.unwrap(), storage.count(&FilterParams::default()).await.unwrap()) };
+        let (found, count) = timeout(Duration::from_secs(1), read).await.expect("read blocked by another reader");
+        assert_eq!(found.map(|e| e.id), Some(1));
+        assert_eq!(count, 1);
+
+        // A writer does wait for it
+        assert!(timeout(Duration::from_millis(50), storage.insert(&entry(2))).await.is_err());
+
+        release_tx.send(()).unwrap();
+        reader.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn insert_evicts_the_oldest_entry() {
+        let max_memory_logs = 5;
+        let db: LogDb = Arc::default();
+        let storage = memory_storage(db.clone(), max_memory_logs);
+        for id in 1..=max_memory_logs as u64 + 1 {
+            storage.insert(&entry(id)).await.unwrap();
+        }
+
+        let ids: Vec<u64> = db.read().await.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [2, 3, 4, 5, 6]);
+        assert!(storage.get(1).await.unwrap().is_none());
+        assert!(storage.get(max_memory_logs as u64 + 1).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn evict_overflow_pops_from_the_front() {
+        let mut logs: VecDeque<LogEntry> = (1..=4).map(entry).collect();
+        evict_overflow(&mut logs, 2);
+        assert_eq!(logs.iter().map(|e| e.id).collect::<Vec<_>>(), [3, 4]);
+
+        evict_overflow(&mut logs, 10);
+        assert_eq!(logs.len(), 2);
+    }
+}
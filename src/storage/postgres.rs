@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::Deserialize;
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::types::Json;
+use sqlx::{Postgres, QueryBuilder, Row};
+use std::collections::HashMap;
+
+use super::{Storage, StorageError};
+use crate::filters::{levels_from, FilterParams};
+use crate::namespace::DEFAULT_NAMESPACE;
+use crate::LogEntry;
+
+const SELECT_COLUMNS: &str =
+    "SELECT id, timestamp, service, level, message, metadata, trace_id, span_id, count, namespace FROM logs";
+
+const INSERT_PREFIX: &str =
+    "INSERT INTO logs (id, timestamp, service, level, message, metadata, trace_id, span_id, count, namespace) ";
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct PostgresConfig {
+    pub(crate) url: String,
+    #[serde(default = "default_max_connections")]
+    pub(crate) max_connections: u32,
+}
+
+fn default_max_connections() -> u32 {
+    10
+}
+
+// Logs kept in a Postgres table created by the migrations in migrations/
+pub(crate) struct PostgresStorage {
+    pool: PgPool,
+}
+
+impl PostgresStorage {
+    pub(crate) async fn connect(cfg: &PostgresConfig) -> Result<Self, StorageError> {
+        let pool = PgPoolOptions::new().max_connections(cfg.max_connections.max(1)).connect(&cfg.url).await?;
+        sqlx::migrate!().run(&pool).await.map_err(sqlx::Error::from)?;
+        Ok(Self { pool })
+    }
+
+    // Highest stored id, so the sequence continues where the table left off
+    pub(crate) async fn max_id(&self) -> Result<u64, StorageError> {
+        let max: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM logs").fetch_one(&self.pool).await?;
+        Ok(max.unwrap_or(0) as u64)
+    }
+}
+
+// Every filter but the message and metadata ones can be answered by SQL
+fn push_filters(qb: &mut QueryBuilder<'_, Postgres>, filters: &FilterParams) {
+    qb.push(" WHERE TRUE");
+    if let Some(service) = &filters.service {
+        qb.push(" AND service = ").push_bind(service.clone());
+    }
+    if let Some(level) = &filters.level {
+        qb.push(" AND level = ").push_bind(level.clone());
+    }
+    // Stored levels are canonical, so the list matches exactly what matches() lets through
+    if let Some(min_severity) = filters.min_severity {
+        let mut levels = qb.push(" AND level IN (").separated(", ");
+        for level in levels_from(min_severity) {
+            levels.push_bind(level.to_string());
+        }
+        levels.push_unseparated(")");
+    }
+    if let Some(trace_id) = &filters.trace_id {
+        qb.push(" AND trace_id = ").push_bind(trace_id.clone());
+    }
+    if let Some(namespace) = &filters.namespace {
+        if namespace == DEFAULT_NAMESPACE {
+            qb.push(" AND (namespace IS NULL OR namespace = ").push_bind(namespace.clone()).push(")");
+        } else {
+            qb.push(" AND namespace = ").push_bind(namespace.clone());
+        }
+    }
+    if let Some(since) = filters.since {
+        qb.push(" AND timestamp >= ").push_bind(since.with_timezone(&Utc));
+    }
+    if let Some(until) = filters.until {
+        qb.push(" AND timestamp <= ").push_bind(until.with_timezone(&Utc));
+    }
+}
+
+// Whether push_filters alone selects exactly the matching rows
+fn sql_only(filters: &FilterParams) -> bool {
+    filters.q.is_none() && filters.q_regex.is_none() && filters.metadata.is_empty()
+}
+
+fn entry_from_row(row: &PgRow) -> Result<LogEntry, sqlx::Error> {
+    let timestamp: Option<DateTime<Utc>> = row.try_get("timestamp")?;
+    let metadata: Option<Json<HashMap<String, serde_json::Value>>> = row.try_get("metadata")?;
+
+    Ok(LogEntry {
+        id: row.try_get::<i64, _>("id")? as u64,
+        // Unparseable timestamps were stored as NULL
+        timestamp: timestamp.map(|ts| ts.to_rfc3339()).unwrap_or_default(),
+        service: row.try_get("service")?,
+        level: row.try_get("level")?,
+        message: row.try_get("message")?,
+        metadata: metadata.map(|m| m.0),
+        trace_id: row.try_get("trace_id")?,
+        span_id: row.try_get("span_id")?,
+        count: row.try_get::<i64, _>("count")? as usize,
+        namespace: row.try_get("namespace")?,
+    })
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn insert(&self, entry: &LogEntry) -> Result<(), StorageError> {
+        self.insert_many(std::slice::from_ref(entry)).await
+    }
+
+    async fn insert_many(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+        let mut tx = self.pool.begin().await?;
+        // Ten parameters a row keeps each chunk well under Postgres's limit of 65535
+        for chunk in entries.chunks(1000) {
+            let mut qb = QueryBuilder::new(INSERT_PREFIX);
+            qb.push_values(chunk, |mut row, entry| {
+                let timestamp = DateTime::parse_from_rfc3339(&entry.timestamp).ok().map(|ts| ts.with_timezone(&Utc));
+                row.push_bind(entry.id as i64)
+                    .push_bind(timestamp)
+                    .push_bind(entry.service.clone())
+                    .push_bind(entry.level.clone())
+                    .push_bind(entry.message.clone())
+                    .push_bind(entry.metadata.clone().map(Json))
+                    .push_bind(entry.trace_id.clone())
+                    .push_bind(entry.span_id.clone())
+                    .push_bind(entry.count as i64)
+                    .push_bind(entry.namespace.clone());
+            });
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn count(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        if !sql_only(filters) {
+            let mut count = 0;
+            self.scan(filters, &mut |_| count += 1).await?;
+            return Ok(count);
+        }
+
+        let mut qb = QueryBuilder::new("SELECT COUNT(*) FROM logs");
+        push_filters(&mut qb, filters);
+        let count: i64 = qb.build_query_scalar().fetch_one(&self.pool).await?;
+        Ok(count as usize)
+    }
+
+    async fn scan(
+        &self,
+        filters: &FilterParams,
+        visit: &mut (dyn for<'e> FnMut(&'e LogEntry) + Send),
+    ) -> Result<(), StorageError> {
+        let mut qb = QueryBuilder::new(SELECT_COLUMNS);
+        push_filters(&mut qb, filters);
+        qb.push(" ORDER BY id");
+
+        let mut rows = qb.build().fetch(&self.pool);
+        while let Some(row) = rows.try_next().await? {
+            match entry_from_row(&row) {
+                Ok(entry) if filters.matches(&entry) => visit(&entry),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "postgres skipping undecodable row"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), StorageError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<LogEntry>, StorageError> {
+        let sql = format!("{} WHERE id = $1", SELECT_COLUMNS);
+        let row = sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool).await?;
+        Ok(row.map(|row| entry_from_row(&row)).transpose()?)
+    }
+
+    async fn delete(&self, id: u64) -> Result<bool, StorageError> {
+        let result = sqlx::query("DELETE FROM logs WHERE id = $1").bind(id as i64).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn clear(&self, service: Option<&str>) -> Result<(), StorageError> {
+        match service {
+            Some(service) => sqlx::query("DELETE FROM logs WHERE service = $1").bind(service).execute(&self.pool).await?,
+            None => sqlx::query("DELETE FROM logs").execute(&self.pool).await?,
+        };
+        Ok(())
+    }
+
+    async fn increment_count(&self, id: u64, by: usize) -> Result<Option<LogEntry>, StorageError> {
+        let sql = "UPDATE logs SET count = count + $1 WHERE id = $2 \
+                   RETURNING id, timestamp, service, level, message, metadata, trace_id, span_id, count, namespace";
+        let row = sqlx::query(sql).bind(by as i64).bind(id as i64).fetch_optional(&self.pool).await?;
+        Ok(row.map(|row| entry_from_row(&row)).transpose()?)
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        apply: &(dyn for<'e> Fn(&'e mut LogEntry) + Send + Sync),
+    ) -> Result<Option<LogEntry>, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!("{} WHERE id = $1 FOR UPDATE", SELECT_COLUMNS);
+        let Some(row) = sqlx::query(&sql).bind(id as i64).fetch_optional(&mut *tx).await? else {
+            return Ok(None);
+        };
+        let mut entry = entry_from_row(&row)?;
+        apply(&mut entry);
+
+        sqlx::query("UPDATE logs SET message = $1, metadata = $2 WHERE id = $3")
+            .bind(entry.message.clone())
+            .bind(entry.metadata.clone().map(Json))
+            .bind(id as i64)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(entry))
+    }
+
+    async fn purge(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        if sql_only(filters) {
+            let mut qb = QueryBuilder::new("DELETE FROM logs");
+            push_filters(&mut qb, filters);
+            return Ok(qb.build().execute(&self.pool).await?.rows_affected() as usize);
+        }
+
+        let mut ids = Vec::new();
+        self.scan(filters, &mut |log| ids.push(log.id as i64)).await?;
+        let result = sqlx::query("DELETE FROM logs WHERE id = ANY($1)").bind(&ids).execute(&self.pool).await?;
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn evict_older_than(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError> {
+        // NULL timestamps never compare as older, so those rows are kept
+        let result = sqlx::query("DELETE FROM logs WHERE timestamp < $1").bind(cutoff).execute(&self.pool).await?;
+        Ok(result.rows_affected() as usize)
+    }
+}
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::Deserialize;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{Storage, StorageError};
+use crate::filters::FilterParams;
+use crate::LogEntry;
+
+// Every entry, newest at the head
+const ALL_KEY: &str = "logs:all";
+// Names of every service with a list of its own
+const SERVICES_KEY: &str = "logs:services";
+
+// Entries fetched per LRANGE while walking a list
+const SCAN_CHUNK: isize = 1000;
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct RedisConfig {
+    pub(crate) url: String,
+    // Multiplexed connections shared round-robin between requests
+    #[serde(default = "default_max_connections")]
+    pub(crate) max_connections: usize,
+}
+
+fn default_max_connections() -> usize {
+    4
+}
+
+// Namespaced so a service called "all" or "services" cannot collide with the shared keys
+fn service_key(service: &str) -> String {
+    format!("logs:service:{}", service)
+}
+
+// Entries kept as JSON in Redis lists, one for everything and one per service, so several
+// aggregators can share a store; each list is capped at max_memory_logs
+pub(crate) struct RedisStorage {
+    connections: Vec<ConnectionManager>,
+    next: AtomicUsize,
+    max_logs: usize,
+}
+
+impl RedisStorage {
+    pub(crate) async fn connect(cfg: &RedisConfig, max_logs: usize) -> Result<Self, StorageError> {
+        let client = redis::Client::open(cfg.url.as_str())?;
+        let mut connections = Vec::with_capacity(cfg.max_connections.max(1));
+        for _ in 0..cfg.max_connections.max(1) {
+            connections.push(ConnectionManager::new(client.clone()).await?);
+        }
+        Ok(Self { connections, next: AtomicUsize::new(0), max_logs: max_logs.max(1) })
+    }
+
+    // Id of the newest entry, so the sequence continues where the list left off
+    pub(crate) async fn max_id(&self) -> Result<u64, StorageError> {
+        let newest: Option<String> = self.conn().lindex(ALL_KEY, 0).await?;
+        Ok(newest.and_then(|raw| serde_json::from_str::<LogEntry>(&raw).ok()).map_or(0, |entry| entry.id))
+    }
+
+    // A cheap handle onto one of the shared connections, taken per operation
+    fn conn(&self) -> ConnectionManager {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+        self.connections[i].clone()
+    }
+
+    // Hand each stored JSON string and its entry to visit, oldest first
+    async fn scan_raw(
+        &self,
+        key: &str,
+        visit: &mut (dyn FnMut(&str, &LogEntry) + Send),
+    ) -> Result<(), StorageError> {
+        let mut conn = self.conn();
+        // Counting from the tail keeps positions stable while new entries are pushed at the head
+        let mut from_tail: isize = 0;
+        loop {
+            let chunk: Vec<String> = conn.lrange(key, -(from_tail + SCAN_CHUNK), -(from_tail + 1)).await?;
+            for raw in chunk.iter().rev() {
+                match serde_json::from_str::<LogEntry>(raw) {
+                    Ok(entry) => visit(raw, &entry),
+                    Err(e) => tracing::warn!(key, error = %e, "redis skipping undecodable entry"),
+                }
+            }
+            if (chunk.len() as isize) < SCAN_CHUNK {
+                return Ok(());
+            }
+            from_tail += SCAN_CHUNK;
+        }
+    }
+
+    // Lists have no index by id, so walk logs:all from the newest entry, where duplicates usually are
+    async fn find_raw(&self, id: u64) -> Result<Option<(String, LogEntry)>, StorageError> {
+        let mut conn = self.conn();
+        let mut start: isize = 0;
+        loop {
+            let chunk: Vec<String> = conn.lrange(ALL_KEY, start, start + SCAN_CHUNK - 1).await?;
+            let found = chunk.iter().try_for_each(|raw| match serde_json::from_str::<LogEntry>(raw) {
+                Ok(entry) if entry.id == id => ControlFlow::Break((raw.clone(), entry)),
+                _ => ControlFlow::Continue(()),
+            });
+            if let ControlFlow::Break(found) = found {
+                return Ok(Some(found));
+            }
+            if (chunk.len() as isize) < SCAN_CHUNK {
+                return Ok(None);
+            }
+            start += SCAN_CHUNK;
+        }
+    }
+
+    // Remove stored entries from logs:all and their service lists; returns how many left logs:all
+    async fn remove_raw(&self, entries: &[(String, String)]) -> Result<usize, StorageError> {
+        let mut conn = self.conn();
+        let mut removed = 0;
+        for chunk in entries.chunks(SCAN_CHUNK as usize) {
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            for (service, raw) in chunk {
+                pipe.lrem(ALL_KEY, 1, raw).lrem(service_key(service), 1, raw).ignore();
+            }
+            let counts: Vec<usize> = pipe.query_async(&mut conn).await?;
+            removed += counts.iter().sum::<usize>();
+        }
+        Ok(removed)
+    }
+
+    async fn remove_matching(&self, doomed: impl Fn(&LogEntry) -> bool + Send + Sync) -> Result<usize, StorageError> {
+        let mut found = Vec::new();
+        self.scan_raw(ALL_KEY, &mut |raw, entry| {
+            if doomed(entry) {
+                found.push((entry.service.clone(), raw.to_string()));
+            }
+        })
+        .await?;
+        self.remove_raw(&found).await
+    }
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn insert(&self, entry: &LogEntry) -> Result<(), StorageError> {
+        self.insert_many(std::slice::from_ref(entry)).await
+    }
+
+    async fn insert_many(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        let mut services = std::collections::HashSet::new();
+        for entry in entries {
+            let Ok(raw) = serde_json::to_string(entry) else {
+                continue;
+            };
+            pipe.lpush(ALL_KEY, &raw).ignore().lpush(service_key(&entry.service), &raw).ignore();
+            services.insert(entry.service.as_str());
+        }
+        let cap = self.max_logs as isize - 1;
+        pipe.ltrim(ALL_KEY, 0, cap).ignore();
+        for service in services {
+            pipe.sadd(SERVICES_KEY, service).ignore().ltrim(service_key(service), 0, cap).ignore();
+        }
+
+        pipe.query_async::<()>(&mut self.conn()).await?;
+        Ok(())
+    }
+
+    async fn scan(
+        &self,
+        filters: &FilterParams,
+        visit: &mut (dyn for<'e> FnMut(&'e LogEntry) + Send),
+    ) -> Result<(), StorageError> {
+        // A service filter only has to read that service's list
+        let key = match &filters.service {
+            Some(service) => service_key(service),
+            None => ALL_KEY.to_string(),
+        };
+        self.scan_raw(&key, &mut |_, entry| {
+            if filters.matches(entry) {
+                visit(entry);
+            }
+        })
+        .await
+    }
+
+    async fn health_check(&self) -> Result<(), StorageError> {
+        redis::cmd("PING").query_async::<String>(&mut self.conn()).await?;
+        Ok(())
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<LogEntry>, StorageError> {
+        Ok(self.find_raw(id).await?.map(|(_, entry)| entry))
+    }
+
+    async fn delete(&self, id: u64) -> Result<bool, StorageError> {
+        match self.find_raw(id).await? {
+            Some((raw, entry)) => Ok(self.remove_raw(&[(entry.service, raw)]).await? > 0),
+            None => Ok(false),
+        }
+    }
+
+    async fn clear(&self, service: Option<&str>) -> Result<(), StorageError> {
+        let mut conn = self.conn();
+        match service {
+            Some(service) => {
+                let raws: Vec<String> = conn.lrange(service_key(service), 0, -1).await?;
+                let entries: Vec<(String, String)> = raws.into_iter().map(|raw| (service.to_string(), raw)).collect();
+                self.remove_raw(&entries).await?;
+                redis::pipe()
+                    .atomic()
+                    .del(service_key(service))
+                    .srem(SERVICES_KEY, service)
+                    .query_async::<()>(&mut conn)
+                    .await?;
+            }
+            None => {
+                let services: Vec<String> = conn.smembers(SERVICES_KEY).await?;
+                let mut keys: Vec<String> = services.iter().map(|s| service_key(s)).collect();
+                keys.push(ALL_KEY.to_string());
+                keys.push(SERVICES_KEY.to_string());
+                conn.del::<_, ()>(keys).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn increment_count(&self, id: u64, by: usize) -> Result<Option<LogEntry>, StorageError> {
+        self.update(id, &|entry| entry.count += by).await
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        apply: &(dyn for<'e> Fn(&'e mut LogEntry) + Send + Sync),
+    ) -> Result<Option<LogEntry>, StorageError> {
+        let Some((raw, mut entry)) = self.find_raw(id).await? else {
+            return Ok(None);
+        };
+        apply(&mut entry);
+        let Ok(updated) = serde_json::to_string(&entry) else {
+            return Ok(None);
+        };
+
+        // Insert the new form next to the old one before dropping it, so the entry keeps its place
+        let service = service_key(&entry.service);
+        let (inserted, _, _, _): (isize, usize, isize, usize) = redis::pipe()
+            .atomic()
+            .linsert_before(ALL_KEY, &raw, &updated)
+            .lrem(ALL_KEY, 1, &raw)
+            .linsert_before(&service, &raw, &updated)
+            .lrem(&service, 1, &raw)
+            .query_async(&mut self.conn())
+            .await?;
+
+        // A negative LINSERT reply means the entry was removed in the meantime
+        Ok((inserted > 0).then_some(entry))
+    }
+
+    async fn purge(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        self.remove_matching(|entry| filters.matches(entry)).await
+    }
+
+    async fn evict_older_than(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError> {
+        self.remove_matching(|entry| DateTime::parse_from_rfc3339(&entry.timestamp).is_ok_and(|ts| ts < cutoff))
+            .await
+    }
+}
@@ -0,0 +1,316 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqliteRow};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use std::str::FromStr;
+
+use super::{Storage, StorageError};
+use crate::filters::{levels_from, FilterParams};
+use crate::namespace::DEFAULT_NAMESPACE;
+use crate::LogEntry;
+
+const SELECT_COLUMNS: &str =
+    "SELECT id, timestamp, service, level, message, metadata, trace_id, span_id, count, namespace FROM logs";
+
+// Columns added after the original five-column schema, created on upgrade
+const EXTRA_COLUMNS: [(&str, &str); 5] = [
+    ("metadata", "TEXT"),
+    ("trace_id", "TEXT"),
+    ("span_id", "TEXT"),
+    ("count", "INTEGER NOT NULL DEFAULT 1"),
+    ("namespace", "TEXT"),
+];
+
+// Logs kept in a SQLite table, so filters on indexed columns never scan the whole store
+pub(crate) struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub(crate) async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS logs (
+                id INTEGER PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                service TEXT NOT NULL,
+                level TEXT NOT NULL,
+                message TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        let existing: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('logs')")
+            .fetch_all(&pool)
+            .await?;
+        for (column, definition) in EXTRA_COLUMNS {
+            if !existing.iter().any(|c| c == column) {
+                sqlx::query(&format!("ALTER TABLE logs ADD COLUMN {} {}", column, definition))
+                    .execute(&pool)
+                    .await?;
+            }
+        }
+
+        for column in ["service", "level", "timestamp", "trace_id", "namespace"] {
+            sqlx::query(&format!("CREATE INDEX IF NOT EXISTS idx_logs_{0} ON logs ({0})", column))
+                .execute(&pool)
+                .await?;
+        }
+
+        Ok(Self { pool })
+    }
+
+    // Highest stored id, so the sequence continues where the table left off
+    pub(crate) async fn max_id(&self) -> Result<u64, sqlx::Error> {
+        let max: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM logs").fetch_one(&self.pool).await?;
+        Ok(max.unwrap_or(0) as u64)
+    }
+}
+
+// Narrow the rows in SQL where possible; matches() still has the final say
+fn push_filters(qb: &mut QueryBuilder<'_, Sqlite>, filters: &FilterParams) {
+    qb.push(" WHERE 1 = 1");
+    if let Some(service) = &filters.service {
+        qb.push(" AND service = ").push_bind(service.clone());
+    }
+    if let Some(level) = &filters.level {
+        qb.push(" AND level = ").push_bind(level.clone());
+    }
+    // Stored levels are canonical, so the list matches exactly what matches() lets through
+    if let Some(min_severity) = filters.min_severity {
+        let mut levels = qb.push(" AND level IN (").separated(", ");
+        for level in levels_from(min_severity) {
+            levels.push_bind(level.to_string());
+        }
+        levels.push_unseparated(")");
+    }
+    if let Some(trace_id) = &filters.trace_id {
+        qb.push(" AND trace_id = ").push_bind(trace_id.clone());
+    }
+    if let Some(namespace) = &filters.namespace {
+        if namespace == DEFAULT_NAMESPACE {
+            qb.push(" AND (namespace IS NULL OR namespace = ").push_bind(namespace.clone()).push(")");
+        } else {
+            qb.push(" AND namespace = ").push_bind(namespace.clone());
+        }
+    }
+    // julianday only keeps millisecond precision, so leave a second of slack either side
+    if let Some(since) = filters.since {
+        qb.push(" AND julianday(timestamp) >= julianday(")
+            .push_bind((since - chrono::TimeDelta::seconds(1)).to_rfc3339())
+            .push(")");
+    }
+    if let Some(until) = filters.until {
+        qb.push(" AND julianday(timestamp) <= julianday(")
+            .push_bind((until + chrono::TimeDelta::seconds(1)).to_rfc3339())
+            .push(")");
+    }
+}
+
+// Whether push_filters alone selects exactly the matching rows
+fn sql_only(filters: &FilterParams) -> bool {
+    filters.q.is_none()
+        && filters.q_regex.is_none()
+        && filters.since.is_none()
+        && filters.until.is_none()
+        && filters.metadata.is_empty()
+}
+
+fn entry_from_row(row: &SqliteRow) -> Result<LogEntry, sqlx::Error> {
+    let metadata = match row.try_get::<Option<String>, _>("metadata")? {
+        Some(json) => Some(serde_json::from_str(&json).map_err(|e| sqlx::Error::Decode(Box::new(e)))?),
+        None => None,
+    };
+
+    Ok(LogEntry {
+        id: row.try_get::<i64, _>("id")? as u64,
+        timestamp: row.try_get("timestamp")?,
+        service: row.try_get("service")?,
+        level: row.try_get("level")?,
+        message: row.try_get("message")?,
+        metadata,
+        trace_id: row.try_get("trace_id")?,
+        span_id: row.try_get("span_id")?,
+        count: row.try_get::<i64, _>("count")? as usize,
+        namespace: row.try_get("namespace")?,
+    })
+}
+
+fn push_entry_values(qb: &mut QueryBuilder<'_, Sqlite>, entry: &LogEntry) {
+    let metadata = entry.metadata.as_ref().and_then(|m| serde_json::to_string(m).ok());
+    qb.push("(")
+        .push_bind(entry.id as i64)
+        .push(", ")
+        .push_bind(entry.timestamp.clone())
+        .push(", ")
+        .push_bind(entry.service.clone())
+        .push(", ")
+        .push_bind(entry.level.clone())
+        .push(", ")
+        .push_bind(entry.message.clone())
+        .push(", ")
+        .push_bind(metadata)
+        .push(", ")
+        .push_bind(entry.trace_id.clone())
+        .push(", ")
+        .push_bind(entry.span_id.clone())
+        .push(", ")
+        .push_bind(entry.count as i64)
+        .push(", ")
+        .push_bind(entry.namespace.clone())
+        .push(")");
+}
+
+const INSERT_PREFIX: &str =
+    "INSERT INTO logs (id, timestamp, service, level, message, metadata, trace_id, span_id, count, namespace) VALUES ";
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn insert(&self, entry: &LogEntry) -> Result<(), StorageError> {
+        let mut qb = QueryBuilder::new(INSERT_PREFIX);
+        push_entry_values(&mut qb, entry);
+        qb.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn insert_many(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+        // One transaction, so a failed chunk leaves none of the batch behind
+        let mut tx = self.pool.begin().await?;
+        // Stay well under SQLite's bound-parameter limit of 32766
+        for chunk in entries.chunks(1000) {
+            let mut qb = QueryBuilder::new(INSERT_PREFIX);
+            for (i, entry) in chunk.iter().enumerate() {
+                if i > 0 {
+                    qb.push(", ");
+                }
+                push_entry_values(&mut qb, entry);
+            }
+            qb.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn count(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        if !sql_only(filters) {
+            let mut count = 0;
+            self.scan(filters, &mut |_| count += 1).await?;
+            return Ok(count);
+        }
+
+        let mut qb = QueryBuilder::new("SELECT COUNT(*) FROM logs");
+        push_filters(&mut qb, filters);
+        let count = qb.build_query_scalar::<i64>().fetch_one(&self.pool).await?;
+        Ok(count as usize)
+    }
+
+    async fn scan(
+        &self,
+        filters: &FilterParams,
+        visit: &mut (dyn for<'e> FnMut(&'e LogEntry) + Send),
+    ) -> Result<(), StorageError> {
+        let mut qb = QueryBuilder::new(SELECT_COLUMNS);
+        push_filters(&mut qb, filters);
+        qb.push(" ORDER BY id");
+
+        let mut rows = qb.build().fetch(&self.pool);
+        while let Some(row) = rows.try_next().await? {
+            match entry_from_row(&row) {
+                Ok(entry) if filters.matches(&entry) => visit(&entry),
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "sqlite skipping undecodable row"),
+            }
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), StorageError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn get(&self, id: u64) -> Result<Option<LogEntry>, StorageError> {
+        let sql = format!("{} WHERE id = ?", SELECT_COLUMNS);
+        let row = sqlx::query(&sql).bind(id as i64).fetch_optional(&self.pool).await?;
+        Ok(row.map(|row| entry_from_row(&row)).transpose()?)
+    }
+
+    async fn delete(&self, id: u64) -> Result<bool, StorageError> {
+        let result = sqlx::query("DELETE FROM logs WHERE id = ?").bind(id as i64).execute(&self.pool).await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn clear(&self, service: Option<&str>) -> Result<(), StorageError> {
+        match service {
+            Some(service) => sqlx::query("DELETE FROM logs WHERE service = ?").bind(service).execute(&self.pool).await?,
+            None => sqlx::query("DELETE FROM logs").execute(&self.pool).await?,
+        };
+        Ok(())
+    }
+
+    async fn increment_count(&self, id: u64, by: usize) -> Result<Option<LogEntry>, StorageError> {
+        let result = sqlx::query("UPDATE logs SET count = count + ? WHERE id = ?")
+            .bind(by as i64)
+            .bind(id as i64)
+            .execute(&self.pool)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+        self.get(id).await
+    }
+
+    async fn update(
+        &self,
+        id: u64,
+        apply: &(dyn for<'e> Fn(&'e mut LogEntry) + Send + Sync),
+    ) -> Result<Option<LogEntry>, StorageError> {
+        let mut tx = self.pool.begin().await?;
+        let sql = format!("{} WHERE id = ?", SELECT_COLUMNS);
+        let Some(row) = sqlx::query(&sql).bind(id as i64).fetch_optional(&mut *tx).await? else {
+            return Ok(None);
+        };
+        let mut entry = entry_from_row(&row)?;
+        apply(&mut entry);
+
+        let metadata = entry.metadata.as_ref().and_then(|m| serde_json::to_string(m).ok());
+        sqlx::query("UPDATE logs SET message = ?, metadata = ? WHERE id = ?")
+            .bind(entry.message.clone())
+            .bind(metadata)
+            .bind(id as i64)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(entry))
+    }
+
+    async fn purge(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+        let mut ids = Vec::new();
+        self.scan(filters, &mut |log| ids.push(log.id as i64)).await?;
+
+        let mut purged = 0;
+        for chunk in ids.chunks(1000) {
+            let mut qb = QueryBuilder::new("DELETE FROM logs WHERE id IN (");
+            let mut separated = qb.separated(", ");
+            for id in chunk {
+                separated.push_bind(*id);
+            }
+            qb.push(")");
+            purged += qb.build().execute(&self.pool).await?.rows_affected() as usize;
+        }
+        Ok(purged)
+    }
+
+    async fn evict_older_than(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError> {
+        // julianday is NULL for unparseable timestamps, so those rows are kept
+        let result = sqlx::query("DELETE FROM logs WHERE julianday(timestamp) < julianday(?)")
+            .bind(cutoff.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() as usize)
+    }
+}
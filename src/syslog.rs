@@ -0,0 +1,101 @@
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeDelta, Utc};
+use prometheus::IntCounter;
+use std::collections::HashMap;
+use tokio::net::UdpSocket;
+
+use crate::ingest::IngestContext;
+use crate::LogEntry;
+
+// Largest possible UDP payload
+const MAX_DATAGRAM_BYTES: usize = 65_535;
+
+// Receive RFC 3164 datagrams until the process exits; anything unparseable is counted and dropped
+pub(crate) async fn udp_syslog_task(socket: UdpSocket, ctx: IngestContext, parse_errors: IntCounter) {
+    let mut buf = vec![0u8; MAX_DATAGRAM_BYTES];
+
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(e) => {
+                tracing::warn!(error = %e, "udp syslog receive failed");
+                continue;
+            }
+        };
+
+        let Some(entry) = std::str::from_utf8(&buf[..len]).ok().and_then(|text| parse_rfc3164(text, Utc::now())) else {
+            parse_errors.inc();
+            continue;
+        };
+        if ctx.ingest(entry).await.is_err() {
+            parse_errors.inc();
+        }
+    }
+}
+
+// <PRI>Mmm dd hh:mm:ss HOSTNAME TAG: MESSAGE, with the hostname as the service
+fn parse_rfc3164(datagram: &str, now: DateTime<Utc>) -> Option<LogEntry> {
+    let rest = datagram.trim_end_matches(['\r', '\n', '\0']).strip_prefix('<')?;
+    let (pri, rest) = rest.split_once('>')?;
+    if pri.is_empty() || pri.len() > 3 || !pri.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let pri: u8 = pri.parse().ok().filter(|p| *p <= 191)?;
+    let (facility, severity) = (pri / 8, pri % 8);
+
+    // The timestamp is a fixed 15 characters, days below 10 padded with a space
+    let timestamp = parse_timestamp(rest.get(..15)?, now)?;
+    let rest = rest.get(15..)?.strip_prefix(' ')?;
+
+    let (hostname, rest) = rest.split_once(' ')?;
+    if hostname.is_empty() {
+        return None;
+    }
+
+    // The tag is optional; without one the whole remainder is the message
+    let (tag, message) = match rest.split_once(": ") {
+        Some((tag, message)) if !tag.is_empty() && !tag.contains(' ') => (Some(tag), message),
+        _ => (None, rest),
+    };
+    if message.trim().is_empty() {
+        return None;
+    }
+
+    let level = match severity {
+        0..=2 => "ERROR",
+        3..=4 => "WARN",
+        _ => "INFO",
+    };
+
+    let mut metadata = HashMap::from([("facility".to_string(), serde_json::json!(facility))]);
+    if let Some(tag) = tag {
+        metadata.insert("tag".to_string(), serde_json::json!(tag));
+    }
+
+    Some(LogEntry {
+        id: 0,
+        timestamp: timestamp.to_rfc3339(),
+        service: hostname.to_string(),
+        level: level.to_string(),
+        message: message.to_string(),
+        metadata: Some(metadata),
+        trace_id: None,
+        span_id: None,
+        count: 1,
+        namespace: None,
+    })
+}
+
+// RFC 3164 timestamps carry no year or zone: assume UTC and the most recent matching year
+fn parse_timestamp(raw: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let at = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{} {}", year, raw), "%Y %b %e %H:%M:%S")
+            .ok()
+            .map(|naive| naive.and_utc())
+    };
+
+    // A December message received just after New Year belongs to the previous year
+    match at(now.year())? {
+        ts if ts > now + TimeDelta::days(1) => at(now.year() - 1),
+        ts => Some(ts),
+    }
+}
@@ -0,0 +1,170 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use serde::Deserialize;
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+use tracing_subscriber::util::SubscriberInitExt;
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct ObservabilityConfig {
+    // OTLP/HTTP traces endpoint, e.g. http://localhost:4318/v1/traces
+    pub(crate) otlp_endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub(crate) service_name: String,
+}
+
+fn default_service_name() -> String {
+    "log_aggregator".to_string()
+}
+
+// Operational messages go to stdout, as JSON when LOG_FORMAT=json, filtered by RUST_LOG (default
+// info); with [observability] every span, down to the trace-level ones around ingestion, is also
+// exported over OTLP and the returned provider must be shut down to flush them
+pub(crate) fn init_tracing(observability: Option<&ObservabilityConfig>) -> std::io::Result<Option<SdkTracerProvider>> {
+    let fmt_layer = if std::env::var("LOG_FORMAT").is_ok_and(|f| f.eq_ignore_ascii_case("json")) {
+        tracing_subscriber::fmt::layer().json().boxed()
+    } else {
+        // No colour codes when stdout is redirected to a file
+        tracing_subscriber::fmt::layer().with_ansi(std::io::stdout().is_terminal()).boxed()
+    };
+
+    let provider = observability.map(init_tracer).transpose()?;
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("log_aggregator")));
+
+    tracing_subscriber::registry()
+        .with(fmt_layer.with_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"))))
+        .with(otel_layer)
+        .try_init()
+        .map_err(std::io::Error::other)?;
+
+    Ok(provider)
+}
+
+fn init_tracer(cfg: &ObservabilityConfig) -> std::io::Result<SdkTracerProvider> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(cfg.otlp_endpoint.clone())
+        .build()
+        .map_err(std::io::Error::other)?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(cfg.service_name.clone()).build())
+        .build();
+
+    // Continue traces started by callers that send a traceparent header
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(provider)
+}
+
+// Flush buffered spans; the batch processor blocks while it drains, so keep it off the runtime
+pub(crate) async fn shutdown_tracer(provider: SdkTracerProvider) {
+    let result = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+    if let Ok(Err(e)) = result {
+        tracing::error!(error = %e, "failed to flush spans on shutdown");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use async_trait::async_trait;
+    use chrono::{DateTime, Utc};
+    use std::sync::Arc;
+    use tracing_test::traced_test;
+
+    use crate::filters::FilterParams;
+    use crate::storage::{SharedStorage, Storage, StorageError};
+    use crate::tests::{memory_storage, test_app, test_config, wait_for_logs};
+    use crate::LogEntry;
+
+    // Emits an event from insert_many, so the span the dispatcher wraps that call in shows up in the logs
+    struct TracedStorage(SharedStorage);
+
+    #[async_trait]
+    impl Storage for TracedStorage {
+        async fn insert(&self, entry: &LogEntry) -> Result<(), StorageError> {
+            self.0.insert(entry).await
+        }
+
+        async fn insert_many(&self, entries: &[LogEntry]) -> Result<(), StorageError> {
+            tracing::trace!(entries = entries.len(), "storage insert_many");
+            self.0.insert_many(entries).await
+        }
+
+        async fn scan(
+            &self,
+            filters: &FilterParams,
+            visit: &mut (dyn for<'e> FnMut(&'e LogEntry) + Send),
+        ) -> Result<(), StorageError> {
+            self.0.scan(filters, visit).await
+        }
+
+        async fn get(&self, id: u64) -> Result<Option<LogEntry>, StorageError> {
+            self.0.get(id).await
+        }
+
+        async fn delete(&self, id: u64) -> Result<bool, StorageError> {
+            self.0.delete(id).await
+        }
+
+        async fn clear(&self, service: Option<&str>) -> Result<(), StorageError> {
+            self.0.clear(service).await
+        }
+
+        async fn increment_count(&self, id: u64, by: usize) -> Result<Option<LogEntry>, StorageError> {
+            self.0.increment_count(id, by).await
+        }
+
+        async fn update(
+            &self,
+            id: u64,
+            apply: &(dyn for<'e> Fn(&'e mut LogEntry) + Send + Sync),
+        ) -> Result<Option<LogEntry>, StorageError> {
+            self.0.update(id, apply).await
+        }
+
+        async fn purge(&self, filters: &FilterParams) -> Result<usize, StorageError> {
+            self.0.purge(filters).await
+        }
+
+        async fn evict_older_than(&self, cutoff: DateTime<Utc>) -> Result<usize, StorageError> {
+            self.0.evict_older_than(cutoff).await
+        }
+    }
+
+    #[actix_web::test]
+    #[traced_test]
+    async fn post_log_emits_ingest_spans() {
+        let cfg = test_config("", "");
+        let storage: SharedStorage = Arc::new(TracedStorage(memory_storage(&cfg)));
+        let app = test::init_service(test_app(cfg, storage.clone()).await).await;
+
+        // An epoch timestamp is converted, with a warning, while the entry is validated
+        let entry = serde_json::json!({
+            "timestamp": Utc::now().timestamp(),
+            "service": "checkout",
+            "level": "INFO",
+            "message": "order placed",
+        });
+        let req = test::TestRequest::post().uri("/v1/logs").set_json(entry).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::ACCEPTED);
+        wait_for_logs(&storage, 1).await;
+
+        assert!(logs_contain("post_log{namespace=default}:validation: "));
+        assert!(logs_contain("converted non-standard timestamp"));
+        assert!(logs_contain("dispatch{entries=1}:lock_acquire: "));
+        assert!(logs_contain("storage insert_many"));
+        assert!(logs_contain("dispatch{entries=1}:broadcast{entries=1}: "));
+        assert!(logs_contain("ingested entries stored=1 merged=0"));
+    }
+}
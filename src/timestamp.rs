@@ -0,0 +1,91 @@
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use prometheus::IntCounter;
+use serde::{Deserialize, Deserializer};
+
+use crate::LogEntry;
+
+// Epoch values above this are taken as milliseconds; as seconds they would be past the year 5000
+const EPOCH_MILLIS_ABOVE: i64 = 100_000_000_000;
+
+// Accept a JSON number as well as a string, so epoch timestamps survive deserialisation
+pub(crate) fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        String(String),
+        Number(serde_json::Number),
+    }
+
+    Ok(match Raw::deserialize(deserializer)? {
+        Raw::String(s) => s,
+        Raw::Number(n) => n.to_string(),
+    })
+}
+
+// Try RFC 3339, RFC 2822, "%Y-%m-%d %H:%M:%S" as UTC, then epoch seconds and epoch milliseconds.
+// Returns the instant and the name of the format that matched
+pub(crate) fn parse_timestamp(raw: &str) -> Option<(DateTime<Utc>, &'static str)> {
+    let raw = raw.trim();
+    if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
+        return Some((ts.to_utc(), "RFC 3339"));
+    }
+    if let Ok(ts) = DateTime::parse_from_rfc2822(raw) {
+        return Some((ts.to_utc(), "RFC 2822"));
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S") {
+        return Some((naive.and_utc(), "naive date-time"));
+    }
+
+    let epoch: i64 = raw.parse().ok()?;
+    if epoch.abs() <= EPOCH_MILLIS_ABOVE {
+        DateTime::from_timestamp(epoch, 0).map(|ts| (ts, "Unix epoch seconds"))
+    } else {
+        DateTime::from_timestamp_millis(epoch).map(|ts| (ts, "Unix epoch milliseconds"))
+    }
+}
+
+// What an ingested entry's timestamp is stored as: RFC 3339 is kept verbatim, other formats are
+// converted, and anything unparseable becomes the current time
+pub(crate) fn normalize_timestamp(raw: &str, service: &str) -> String {
+    if DateTime::parse_from_rfc3339(raw.trim()).is_ok() {
+        return raw.trim().to_string();
+    }
+    match parse_timestamp(raw) {
+        Some((ts, format)) => {
+            tracing::warn!(service, format, raw, "converted non-standard timestamp");
+            ts.to_rfc3339()
+        }
+        None => {
+            if !raw.trim().is_empty() {
+                tracing::warn!(service, raw, "unparseable timestamp, using the current time");
+            }
+            Utc::now().to_rfc3339()
+        }
+    }
+}
+
+// Flags entries whose timestamp is further from server time than a client clock should drift
+pub(crate) struct ClockSkewCheck {
+    pub(crate) max_skew: TimeDelta,
+    pub(crate) events: IntCounter,
+}
+
+impl ClockSkewCheck {
+    // Annotates rather than rejects; unparseable timestamps were already replaced by now
+    pub(crate) fn check(&self, entry: &mut LogEntry) {
+        let Ok(ts) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+            return;
+        };
+        let skew = Utc::now().signed_duration_since(ts);
+        if skew.abs() <= self.max_skew {
+            return;
+        }
+
+        self.events.inc();
+        tracing::warn!(service = %entry.service, skew_secs = skew.num_seconds(), "clock skew on ingested entry");
+        entry
+            .metadata
+            .get_or_insert_default()
+            .insert("_clock_skew_warning".to_string(), serde_json::Value::Bool(true));
+    }
+}
@@ -0,0 +1,126 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use serde::Deserialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval, Duration, Instant};
+
+use crate::namespace::{namespace_of, Namespace};
+use crate::{Broadcaster, LogEntry, LogEvent};
+
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+// A client that misses two pings in a row is considered gone
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Client control message, e.g. {"action":"subscribe","service":"auth","level":"ERROR"}
+#[derive(Debug, Deserialize)]
+struct ControlMessage {
+    action: String,
+    #[serde(default)]
+    service: Option<String>,
+    #[serde(default)]
+    level: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Subscription {
+    service: Option<String>,
+    level: Option<String>,
+}
+
+impl Subscription {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        self.service.as_ref().is_none_or(|s| entry.service == *s)
+            && self.level.as_ref().is_none_or(|l| entry.level == *l)
+    }
+}
+
+// GET /logs/ws
+#[utoipa::path(
+    get,
+    path = "/v1/logs/ws",
+    responses(
+        (status = 101, description = "Upgraded; entries arrive as text frames, control messages such as {\"action\":\"subscribe\",\"service\":\"auth\"} filter them"),
+        (status = 400, description = "Not a WebSocket handshake"),
+    ),
+)]
+pub(crate) async fn logs_ws(
+    req: HttpRequest,
+    body: web::Payload,
+    bcast: web::Data<Broadcaster>,
+    namespace: Namespace,
+) -> actix_web::Result<HttpResponse> {
+    let (response, session, msg_stream) = actix_ws::handle(&req, body)?;
+    let rx = bcast.subscribe();
+
+    actix_web::rt::spawn(ws_session(session, msg_stream, rx, namespace.name));
+
+    Ok(response)
+}
+
+// Forward the namespace's broadcast entries to one client until either side goes away
+async fn ws_session(
+    mut session: Session,
+    mut msg_stream: MessageStream,
+    mut rx: broadcast::Receiver<LogEvent>,
+    namespace: String,
+) {
+    let mut subscription = Subscription::default();
+    let mut last_heartbeat = Instant::now();
+    let mut ping = interval(PING_INTERVAL);
+
+    let reason = loop {
+        tokio::select! {
+            msg = rx.recv() => match msg {
+                Ok(event) => {
+                    let Ok(entry) = serde_json::from_str::<LogEntry>(&event.payload) else {
+                        continue;
+                    };
+                    // Updated entries go out as-is, their count tells them apart
+                    if namespace_of(&entry) == namespace
+                        && subscription.matches(&entry)
+                        && session.text(event.payload).await.is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break Some(CloseReason::from(CloseCode::Away)),
+            },
+
+            msg = msg_stream.recv() => match msg {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ControlMessage>(&text) {
+                    Ok(ctrl) if ctrl.action == "subscribe" => {
+                        subscription = Subscription { service: ctrl.service, level: ctrl.level };
+                    }
+                    _ => {
+                        let error = serde_json::json!({ "error": "expected {\"action\":\"subscribe\",...}" });
+                        if session.text(error.to_string()).await.is_err() {
+                            return;
+                        }
+                    }
+                },
+                Some(Ok(Message::Ping(bytes))) => {
+                    last_heartbeat = Instant::now();
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(Message::Pong(_))) => last_heartbeat = Instant::now(),
+                Some(Ok(Message::Close(reason))) => break reason,
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => break None,
+            },
+
+            _ = ping.tick() => {
+                if last_heartbeat.elapsed() > CLIENT_TIMEOUT {
+                    break None;
+                }
+                if session.ping(b"").await.is_err() {
+                    return;
+                }
+            }
+        }
+    };
+
+    let _ = session.close(reason).await;
+}